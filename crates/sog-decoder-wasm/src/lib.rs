@@ -2,17 +2,30 @@ mod info;
 mod types;
 
 pub use crate::types::{JsSogDataV2, JsSplat};
+use sog_decoder::types::SogDataV2;
 use wasm_bindgen::prelude::*;
 
+/// JS error with the stable error code and the chain of causes, e.g.
+/// `[decode_image] failed to decode sh0 image: ...`.
+fn js_error(e: sog_decoder::error::Error) -> JsError {
+    let mut message = format!("[{}] {}", e.code(), e);
+    let mut source = std::error::Error::source(&e);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    JsError::new(&message)
+}
+
 #[wasm_bindgen]
 pub fn unpack(buffer: &[u8]) -> Result<JsSogDataV2, JsError> {
-    let sog = sog_decoder::unpack(buffer)?.into();
+    let sog = sog_decoder::unpack(buffer).map_err(js_error)?.try_into()?;
     Ok(sog)
 }
 
 #[wasm_bindgen]
 pub fn decode(js_sog_data: JsSogDataV2) -> Result<JsSplat, JsError> {
-    let sog = js_sog_data.try_into()?;
-    let splat = sog_decoder::decode(&sog)?.into();
+    let sog: SogDataV2 = js_sog_data.try_into()?;
+    let splat = sog_decoder::decode_v2(&sog).map_err(js_error)?.into();
     Ok(splat)
 }
@@ -1,4 +1,6 @@
-use sog_decoder::types::{Codebook, Means, Quats, Scales, Sh0, ShN, SogDataV2, Splat, Vector3};
+use sog_decoder::types::{
+    Codebook, Means, Quats, Scales, Sh0, ShN, SogData, SogDataV2, Splat, Vector3,
+};
 use wasm_bindgen::JsError;
 use wasm_bindgen::prelude::wasm_bindgen;
 
@@ -254,6 +256,17 @@ impl From<SogDataV2> for JsSogDataV2 {
     }
 }
 
+impl TryFrom<SogData> for JsSogDataV2 {
+    type Error = JsError;
+
+    fn try_from(sog_data: SogData) -> Result<Self, Self::Error> {
+        match sog_data {
+            SogData::V2(sog_data) => Ok(sog_data.into()),
+            SogData::V1(_) => Err(JsError::new("legacy SOG v1 data is not supported")),
+        }
+    }
+}
+
 impl TryFrom<JsSogDataV2> for SogDataV2 {
     type Error = JsError;
 
@@ -266,6 +279,7 @@ impl TryFrom<JsSogDataV2> for SogDataV2 {
             quats: js_sog_data.quats.into(),
             sh_0: js_sog_data.sh_0.try_into()?,
             sh_n: js_sog_data.sh_n.map(|sh_n| sh_n.try_into()).transpose()?,
+            names: None,
         };
         Ok(sog)
     }
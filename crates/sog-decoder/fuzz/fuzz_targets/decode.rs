@@ -0,0 +1,88 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use image_webp::{ColorType, WebPEncoder};
+use libfuzzer_sys::fuzz_target;
+use sog_decoder::types::{Codebook, Means, Quats, Scales, Sh0, ShN, SogDataV2, Vector3};
+
+/// Small RGBA image. Random bytes rarely form a valid WebP, so the pixels are encoded here
+/// and the decoder is exercised on image sizes and pixel values instead.
+#[derive(Debug, Arbitrary)]
+struct Image {
+    width: u8,
+    height: u8,
+    seed: u8,
+}
+
+impl Image {
+    fn encode(&self) -> Vec<u8> {
+        let width = self.width as u32 % 64 + 1;
+        let height = self.height as u32 % 64 + 1;
+        let pixels = (0..width * height * 4)
+            .map(|i| (i as u8).wrapping_mul(self.seed).wrapping_add(self.seed >> 3))
+            .collect::<Vec<_>>();
+        let mut data = Vec::new();
+        WebPEncoder::new(&mut data)
+            .encode(&pixels, width, height, ColorType::Rgba8)
+            .expect("encoding a valid image");
+        data
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+struct ShNInput {
+    count: i32,
+    bands: i32,
+    labels: Image,
+    centroids: Image,
+}
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    count: u32,
+    antialias: bool,
+    mins: [f32; 3],
+    maxs: [f32; 3],
+    codebook: f32,
+    means_l: Image,
+    means_u: Image,
+    scales: Image,
+    quats: Image,
+    sh_0: Image,
+    sh_n: Option<ShNInput>,
+}
+
+fn codebook(step: f32) -> Codebook {
+    Codebook(std::array::from_fn(|i| i as f32 * step))
+}
+
+fuzz_target!(|input: Input| {
+    let sog_data = SogDataV2 {
+        count: input.count,
+        antialias: input.antialias,
+        means: Means {
+            mins: Vector3::new(input.mins[0], input.mins[1], input.mins[2]),
+            maxs: Vector3::new(input.maxs[0], input.maxs[1], input.maxs[2]),
+            means_l: input.means_l.encode(),
+            means_u: input.means_u.encode(),
+        },
+        scales: Scales {
+            codebook: codebook(input.codebook),
+            scales: input.scales.encode(),
+        },
+        quats: Quats(input.quats.encode()),
+        sh_0: Sh0 {
+            codebook: codebook(input.codebook),
+            sh_0: input.sh_0.encode(),
+        },
+        sh_n: input.sh_n.map(|sh_n| ShN {
+            count: sh_n.count,
+            bands: sh_n.bands,
+            codebook: codebook(input.codebook),
+            labels: sh_n.labels.encode(),
+            centroids: sh_n.centroids.encode(),
+        }),
+        names: None,
+    };
+    let _ = sog_decoder::decode_v2(&sog_data);
+});
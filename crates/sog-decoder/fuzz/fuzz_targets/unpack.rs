@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(sog_data) = sog_decoder::unpack(data) {
+        let _ = sog_decoder::decode(&sog_data);
+    }
+});
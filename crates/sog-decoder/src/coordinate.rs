@@ -0,0 +1,271 @@
+use crate::error::{DecodeError, DecodeResult};
+
+/// Direction of a coordinate axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    Right,
+    Left,
+    Up,
+    Down,
+    Forward,
+    Back,
+}
+
+impl Direction {
+    /// The direction in the frame of the SOG data, which is x right, y down and z forward.
+    fn vector(self) -> [f32; 3] {
+        match self {
+            Direction::Right => [1.0, 0.0, 0.0],
+            Direction::Left => [-1.0, 0.0, 0.0],
+            Direction::Down => [0.0, 1.0, 0.0],
+            Direction::Up => [0.0, -1.0, 0.0],
+            Direction::Forward => [0.0, 0.0, 1.0],
+            Direction::Back => [0.0, 0.0, -1.0],
+        }
+    }
+}
+
+/// Coordinate system of the decoded data, given by the direction of each axis.
+///
+/// SOG data is stored like the 3DGS training output, x right, y down and z forward,
+/// which is [`CoordinateSystem::OPENCV`] and the default. Other systems transform positions,
+/// rotations, scales and SH coefficients, so view-dependent color stays consistent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CoordinateSystem {
+    pub x: Direction,
+    pub y: Direction,
+    pub z: Direction,
+}
+
+impl CoordinateSystem {
+    /// x right, y down, z forward. The frame of the SOG data.
+    pub const OPENCV: Self = Self::new(Direction::Right, Direction::Down, Direction::Forward);
+    /// x right, y up, z back. Right-handed Y-up as in glTF and OpenGL.
+    pub const GLTF: Self = Self::new(Direction::Right, Direction::Up, Direction::Back);
+    /// x left, y up, z forward. The SOG frame rotated 180° about z, as the PlayCanvas viewer does.
+    pub const PLAYCANVAS: Self = Self::new(Direction::Left, Direction::Up, Direction::Forward);
+    /// Same as [`CoordinateSystem::GLTF`].
+    pub const Y_UP_RIGHT_HANDED: Self = Self::GLTF;
+    /// x right, y up, z forward, as in Babylon.js and Unity.
+    pub const Y_UP_LEFT_HANDED: Self =
+        Self::new(Direction::Right, Direction::Up, Direction::Forward);
+    /// x right, y forward, z up, as in Blender.
+    pub const Z_UP_RIGHT_HANDED: Self =
+        Self::new(Direction::Right, Direction::Forward, Direction::Up);
+    /// x forward, y right, z up, as in Unreal Engine.
+    pub const Z_UP_LEFT_HANDED: Self =
+        Self::new(Direction::Forward, Direction::Right, Direction::Up);
+
+    pub const fn new(x: Direction, y: Direction, z: Direction) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Rows of the matrix from the SOG frame into this system.
+    fn matrix(&self) -> [[f32; 3]; 3] {
+        [self.x.vector(), self.y.vector(), self.z.vector()]
+    }
+
+    fn determinant(&self) -> f32 {
+        let [a, b, c] = self.matrix();
+        a[0] * (b[1] * c[2] - b[2] * c[1]) - a[1] * (b[0] * c[2] - b[2] * c[0])
+            + a[2] * (b[0] * c[1] - b[1] * c[0])
+    }
+
+    /// Whether the axes point in three different dimensions.
+    pub fn is_valid(&self) -> bool {
+        self.determinant() != 0.0
+    }
+
+    pub fn is_right_handed(&self) -> bool {
+        // the SOG frame, x right, y down and z forward, is right-handed
+        self.determinant() > 0.0
+    }
+}
+
+impl Default for CoordinateSystem {
+    fn default() -> Self {
+        Self::OPENCV
+    }
+}
+
+/// Real SH basis of bands 1 to 3 in the coefficient order of 3DGS.
+fn sh_basis(band: usize, [x, y, z]: [f32; 3]) -> Vec<f32> {
+    let (xx, yy, zz) = (x * x, y * y, z * z);
+    match band {
+        1 => vec![-y, z, -x],
+        2 => vec![
+            x * y,
+            -y * z,
+            0.288_675_13 * (2.0 * zz - xx - yy),
+            -x * z,
+            0.5 * (xx - yy),
+        ],
+        3 => vec![
+            -0.204_124_15 * y * (3.0 * xx - yy),
+            x * y * z,
+            -0.158_113_88 * y * (4.0 * zz - xx - yy),
+            0.129_099_44 * z * (2.0 * zz - 3.0 * xx - 3.0 * yy),
+            -0.158_113_88 * x * (4.0 * zz - xx - yy),
+            0.5 * z * (xx - yy),
+            -0.204_124_15 * x * (xx - 3.0 * yy),
+        ],
+        _ => unreachable!(),
+    }
+}
+
+/// Solve `a * x = b` for a square `a` by Gaussian elimination. `b` is overwritten with `x`.
+fn solve(mut a: Vec<Vec<f32>>, b: &mut [Vec<f32>]) {
+    let n = a.len();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|i, j| a[*i][col].abs().total_cmp(&a[*j][col].abs()))
+            .unwrap_or(col);
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let (pivot_a, pivot_b) = (a[col].clone(), b[col].clone());
+        for row in (0..n).filter(|row| *row != col) {
+            let f = a[row][col] / pivot_a[col];
+            a[row]
+                .iter_mut()
+                .zip(&pivot_a)
+                .for_each(|(v, p)| *v -= f * p);
+            b[row]
+                .iter_mut()
+                .zip(&pivot_b)
+                .for_each(|(v, p)| *v -= f * p);
+        }
+    }
+    for (row, b) in b.iter_mut().enumerate() {
+        b.iter_mut().for_each(|v| *v /= a[row][row]);
+    }
+}
+
+/// Conversion from the SOG frame into a [`CoordinateSystem`].
+pub(crate) struct Transform {
+    matrix: [[f32; 3]; 3],
+    determinant: f32,
+    /// Coefficient matrices of bands 1 to 3, `new = sh[band] * old`.
+    sh: [Vec<Vec<f32>>; 3],
+}
+
+impl Transform {
+    /// `None` for the identity.
+    pub fn new(coordinate_system: &CoordinateSystem) -> DecodeResult<Option<Self>> {
+        if !coordinate_system.is_valid() {
+            return Err(DecodeError::InvalidData(format!(
+                "invalid coordinate system: {:?}",
+                coordinate_system
+            )));
+        }
+        if *coordinate_system == CoordinateSystem::OPENCV {
+            return Ok(None);
+        }
+
+        let matrix = coordinate_system.matrix();
+        let inverse = |d: [f32; 3]| {
+            // the matrix is orthogonal, so the inverse is the transpose
+            [0, 1, 2].map(|i| (0..3).map(|j| matrix[j][i] * d[j]).sum::<f32>())
+        };
+
+        // a function f on directions becomes f' with f'(d) = f(M^T d). The basis is
+        // sampled at generic directions and solved in the least squares sense for each band.
+        let samples = (0..16)
+            .map(|i| {
+                let t = (i as f32 + 0.5) / 16.0;
+                let phi = i as f32 * 2.399_963;
+                let r = (1.0 - (1.0 - 2.0 * t).powi(2)).sqrt();
+                [r * phi.cos(), r * phi.sin(), 1.0 - 2.0 * t]
+            })
+            .collect::<Vec<_>>();
+        let sh = [1, 2, 3].map(|band| {
+            let basis = samples
+                .iter()
+                .map(|d| sh_basis(band, *d))
+                .collect::<Vec<_>>();
+            let rotated = samples
+                .iter()
+                .map(|d| sh_basis(band, inverse(*d)))
+                .collect::<Vec<_>>();
+            let n = band * 2 + 1;
+            let normal = (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| basis.iter().map(|b| b[i] * b[j]).sum())
+                        .collect()
+                })
+                .collect();
+            let mut rhs = (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| basis.iter().zip(&rotated).map(|(b, r)| b[i] * r[j]).sum())
+                        .collect()
+                })
+                .collect::<Vec<Vec<f32>>>();
+            solve(normal, &mut rhs);
+            rhs
+        });
+
+        Ok(Some(Self {
+            matrix,
+            determinant: coordinate_system.determinant(),
+            sh,
+        }))
+    }
+
+    fn apply(&self, v: &[f32]) -> [f32; 3] {
+        self.matrix
+            .map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+    }
+
+    pub fn positions(&self, positions: &mut [f32]) {
+        for p in positions.chunks_exact_mut(3) {
+            let v = self.apply(p);
+            p.copy_from_slice(&v);
+        }
+    }
+
+    /// `w, x, y, z` quaternions. The rotation `M * R * M^T` is proper even if `M` mirrors,
+    /// since mirroring twice cancels out.
+    pub fn rotations(&self, rotations: &mut [f32]) {
+        for q in rotations.chunks_exact_mut(4) {
+            let v = self.apply(&q[1..]);
+            for i in 0..3 {
+                q[i + 1] = v[i] * self.determinant;
+            }
+        }
+    }
+
+    /// Scales along the local axes follow the permutation of the axes without changing sign,
+    /// both as log and as linear scales.
+    pub fn scales(&self, scales: &mut [f32]) {
+        for s in scales.chunks_exact_mut(3) {
+            let v = [0, 1, 2].map(|i| {
+                let j = (0..3).find(|j| self.matrix[i][*j] != 0.0).unwrap_or(i);
+                s[j]
+            });
+            s.copy_from_slice(&v);
+        }
+    }
+
+    /// shN coefficients laid out as in [`Splat::sh_n`](crate::types::Splat::sh_n).
+    pub fn sh_n(&self, sh_n: &mut [f32], coeff_count: usize) {
+        if coeff_count == 0 {
+            return;
+        }
+        let mut old = vec![0f32; coeff_count];
+        for coeffs in sh_n.chunks_exact_mut(coeff_count) {
+            old.copy_from_slice(coeffs);
+            let mut start = 0;
+            for (band, matrix) in self.sh.iter().enumerate() {
+                let n = band * 2 + 3;
+                if start + n > coeff_count {
+                    break;
+                }
+                for (i, row) in matrix.iter().enumerate() {
+                    coeffs[start + i] = row.iter().zip(&old[start..]).map(|(m, c)| m * c).sum();
+                }
+                start += n;
+            }
+        }
+    }
+}
@@ -1,41 +1,100 @@
-﻿use crate::error::{DecodeError, DecodeResult, ParseError, ParseResult, Result, UnzipResult};
-use crate::metajson::MetaJsonType;
-use crate::types::{Means, Quats, Scales, Sh0, ShN, SogDataV2, Splat};
-use image_webp::WebPDecoder;
+use crate::coordinate::{CoordinateSystem, Transform};
+use crate::error::{
+    DecodeError, DecodeResult, Error, Limit, LimitExceeded, ParseError, ParseResult, Result,
+};
+use crate::image::{RgbaImage, decode_webp};
+use crate::kernels;
+use crate::legacy::{decode_v1, parse_sog_v1};
+use crate::limits::Limits;
+use crate::metajson::{SogMeta, SogMetaV1, Version, detect_version};
+use crate::pack::EntryNames;
+use crate::parallel::{for_each_block, for_each_splat, join};
+use crate::rotation::{
+    QuaternionOrder, covariances, normalize_quaternions, reorder_quaternions, rotation_matrices,
+};
+use crate::source::{DirSource, LimitedSource, OwnedFiles, SogSource, ZipSource};
+use crate::types::{
+    Means, QuantizedSogData, Quats, Scales, Sh0, ShN, SogData, SogDataRef, SogDataV2, Splat,
+    activate_colors, activate_scales,
+};
+use crate::validate::{Finding, Location, Severity};
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::{Cursor, Read};
-use zip::ZipArchive;
-use zip::result::ZipError;
+use std::fs::File;
+#[cfg(not(feature = "mmap"))]
+use std::io::BufReader;
+use std::io::{self, Cursor, Read, Seek};
+use std::path::Path;
 
-/// Unzip a zip file and return a HashMap of file names and their contents.
-fn unzip(file_data: &[u8]) -> UnzipResult<HashMap<String, Vec<u8>>> {
-    let cursor = Cursor::new(file_data);
-    let mut archive = ZipArchive::new(cursor)?;
-    let mut files = HashMap::new();
-
-    for i in 0..archive.len() {
-        let mut zip_file = archive.by_index(i)?;
-        let mut buf = Vec::with_capacity(zip_file.size() as usize);
-        let _size = zip_file.read_to_end(&mut buf).map_err(ZipError::Io)?;
-        files.insert(zip_file.name().to_owned(), buf);
+/// [`ZipSource`] and [`LimitedSource`] report exceeded limits as I/O errors wrapping
+/// [`LimitExceeded`].
+fn read_error(name: &str, e: io::Error) -> ParseError {
+    match e.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>()) {
+        Some(limit) => ParseError::LimitExceeded(limit.clone()),
+        None => ParseError::ReadFile(name.to_string(), e),
     }
+}
 
-    Ok(files)
+pub(crate) fn read_image<S: SogSource + ?Sized>(source: &S, name: &str) -> ParseResult<Vec<u8>> {
+    source.read(name).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => ParseError::ImageNotFound(name.to_string()),
+        _ => read_error(name, e),
+    })
 }
 
-fn parse_sog(files: HashMap<String, Vec<u8>>) -> ParseResult<SogDataV2> {
-    let meta_bytes = files.get("meta.json").ok_or(ParseError::MetaJsonNotFound)?;
+/// Prefix vector and codebook errors with the `meta.json` field they come from.
+pub(crate) fn in_field(field: &str) -> impl Fn(ParseError) -> ParseError + '_ {
+    move |e| match e {
+        ParseError::ParseVector(message) => ParseError::ParseVector(format!("{field}: {message}")),
+        ParseError::ParseCodebook(message) => {
+            ParseError::ParseCodebook(format!("{field}: {message}"))
+        }
+        e => e,
+    }
+}
+
+pub(crate) fn read_meta_json<S: SogSource + ?Sized>(source: &S) -> ParseResult<serde_json::Value> {
+    let meta_bytes = source.read("meta.json").map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => ParseError::MetaJsonNotFound,
+        _ => read_error("meta.json", e),
+    })?;
 
-    let meta_json_string = str::from_utf8(meta_bytes)
+    let meta_json_string = str::from_utf8(&meta_bytes)
         .map_err(|_| ParseError::InvalidMetaJson("encoding is not utf8".to_string()))?;
 
-    let meta_json = serde_json::from_str::<MetaJsonType>(meta_json_string)
-        .map_err(ParseError::DeserializeMetaJson)?;
+    serde_json::from_str::<serde_json::Value>(meta_json_string)
+        .map_err(ParseError::DeserializeMetaJson)
+}
 
-    if meta_json.version != 2 {
-        return Err(ParseError::InvalidMetaJson("version is not 2".to_string()));
+fn parse_sog<S: SogSource + ?Sized>(source: &S) -> ParseResult<SogData> {
+    parse_sog_with_meta(source, read_meta_json(source)?)
+}
+
+/// Parse SOG data from an already read `meta.json`.
+pub(crate) fn parse_sog_with_meta<S: SogSource + ?Sized>(
+    source: &S,
+    meta_json: serde_json::Value,
+) -> ParseResult<SogData> {
+    match detect_version(&meta_json)? {
+        Version::V1 => {
+            let meta_json = serde_json::from_value::<SogMetaV1>(meta_json)
+                .map_err(ParseError::DeserializeMetaJson)?;
+            Ok(SogData::V1(parse_sog_v1(source, meta_json)?))
+        }
+        Version::V2 => {
+            let meta_json = serde_json::from_value::<SogMeta>(meta_json)
+                .map_err(ParseError::DeserializeMetaJson)?;
+            Ok(SogData::V2(parse_sog_v2(meta_json, |name| {
+                read_image(source, name)
+            })?))
+        }
     }
+}
 
+fn parse_sog_v2<I>(
+    meta_json: SogMeta,
+    mut read_image: impl FnMut(&str) -> ParseResult<I>,
+) -> ParseResult<SogDataV2<I>> {
     let means_l_name = meta_json
         .means
         .files
@@ -51,16 +110,18 @@ fn parse_sog(files: HashMap<String, Vec<u8>>) -> ParseResult<SogDataV2> {
             "missing means_u file name".to_string(),
         ))?;
     let means = Means {
-        mins: meta_json.means.mins.try_into()?,
-        maxs: meta_json.means.maxs.try_into()?,
-        means_l: files
-            .get(means_l_name)
-            .ok_or(ParseError::ImageNotFound(means_l_name.to_string()))?
-            .clone(),
-        means_u: files
-            .get(means_u_name)
-            .ok_or(ParseError::ImageNotFound(means_u_name.to_string()))?
-            .clone(),
+        mins: meta_json
+            .means
+            .mins
+            .try_into()
+            .map_err(in_field("means.mins"))?,
+        maxs: meta_json
+            .means
+            .maxs
+            .try_into()
+            .map_err(in_field("means.maxs"))?,
+        means_l: read_image(means_l_name)?,
+        means_u: read_image(means_u_name)?,
     };
 
     let scales_name = meta_json
@@ -71,11 +132,13 @@ fn parse_sog(files: HashMap<String, Vec<u8>>) -> ParseResult<SogDataV2> {
             "missing scales file name".to_string(),
         ))?;
     let scales = Scales {
-        codebook: meta_json.scales.codebook.as_slice().try_into()?,
-        scales: files
-            .get(scales_name)
-            .ok_or(ParseError::ImageNotFound(scales_name.to_string()))?
-            .clone(),
+        codebook: meta_json
+            .scales
+            .codebook
+            .as_slice()
+            .try_into()
+            .map_err(in_field("scales.codebook"))?,
+        scales: read_image(scales_name)?,
     };
 
     let quats_name = meta_json
@@ -85,12 +148,7 @@ fn parse_sog(files: HashMap<String, Vec<u8>>) -> ParseResult<SogDataV2> {
         .ok_or(ParseError::InvalidMetaJson(
             "missing quats file name".to_string(),
         ))?;
-    let quats = Quats(
-        files
-            .get(quats_name)
-            .ok_or(ParseError::ImageNotFound(quats_name.to_string()))?
-            .clone(),
-    );
+    let quats = Quats(read_image(quats_name)?);
 
     let sh0_name = meta_json
         .sh0
@@ -100,11 +158,22 @@ fn parse_sog(files: HashMap<String, Vec<u8>>) -> ParseResult<SogDataV2> {
             "missing sh0 file name".to_string(),
         ))?;
     let sh_0 = Sh0 {
-        codebook: meta_json.sh0.codebook.as_slice().try_into()?,
-        sh_0: files
-            .get(sh0_name)
-            .ok_or(ParseError::ImageNotFound(sh0_name.to_string()))?
-            .clone(),
+        codebook: meta_json
+            .sh0
+            .codebook
+            .as_slice()
+            .try_into()
+            .map_err(in_field("sh0.codebook"))?,
+        sh_0: read_image(sh0_name)?,
+    };
+
+    let mut names = EntryNames {
+        means_l: means_l_name.clone(),
+        means_u: means_u_name.clone(),
+        scales: scales_name.clone(),
+        quats: quats_name.clone(),
+        sh_0: sh0_name.clone(),
+        ..EntryNames::default()
     };
 
     let sh_n = if let Some(sh_n) = meta_json.sh_n {
@@ -114,18 +183,18 @@ fn parse_sog(files: HashMap<String, Vec<u8>>) -> ParseResult<SogDataV2> {
         let labels_name = sh_n.files.get(1).ok_or(ParseError::InvalidMetaJson(
             "missing labels file name".to_string(),
         ))?;
+        names.sh_n_centroids = centroids_name.clone();
+        names.sh_n_labels = labels_name.clone();
         Some(ShN {
             count: sh_n.count,
             bands: sh_n.bands,
-            codebook: sh_n.codebook.as_slice().try_into()?,
-            centroids: files
-                .get(centroids_name)
-                .ok_or(ParseError::ImageNotFound(centroids_name.to_string()))?
-                .clone(),
-            labels: files
-                .get(labels_name)
-                .ok_or(ParseError::ImageNotFound(labels_name.to_string()))?
-                .clone(),
+            codebook: sh_n
+                .codebook
+                .as_slice()
+                .try_into()
+                .map_err(in_field("shN.codebook"))?,
+            centroids: read_image(centroids_name)?,
+            labels: read_image(labels_name)?,
         })
     } else {
         None
@@ -139,17 +208,206 @@ fn parse_sog(files: HashMap<String, Vec<u8>>) -> ParseResult<SogDataV2> {
         scales,
         sh_0,
         sh_n,
+        names: Some(names),
     })
 }
 
-pub fn unpack(file: &[u8]) -> Result<SogDataV2> {
-    let files = unzip(file)?;
-    let sog_data = parse_sog(files)?;
+pub fn unpack(file: &[u8]) -> Result<SogData> {
+    unpack_with_limits(file, &Limits::NONE)
+}
+
+/// Same as [`unpack`], enforcing `limits`.
+pub fn unpack_with_limits(file: &[u8], limits: &Limits) -> Result<SogData> {
+    unpack_reader_with_limits(Cursor::new(file), limits)
+}
+
+/// Unpack a bundled `.sog` archive from a reader.
+/// Only `meta.json` and the entries it references are decompressed.
+pub fn unpack_reader<R: Read + Seek>(reader: R) -> Result<SogData> {
+    unpack_reader_with_limits(reader, &Limits::NONE)
+}
+
+/// Same as [`unpack_reader`], enforcing `limits`.
+pub fn unpack_reader_with_limits<R: Read + Seek>(reader: R, limits: &Limits) -> Result<SogData> {
+    let source = ZipSource::new_with_limits(reader, *limits)?;
+    let meta_json = read_meta_json(&source)?;
+    limits.check_splat_count(&meta_json)?;
+    let sog_data = parse_sog_with_meta(&source, meta_json)?;
     Ok(sog_data)
 }
 
-#[allow(clippy::identity_op)]
-fn decode_positions(means: &Means, count: usize) -> DecodeResult<Vec<f32>> {
+/// Unpack a bundled `.sog` archive, dropping shN instead of failing when its `meta.json` block
+/// is malformed or its images are missing. Dropped shN is reported as a warning [`Finding`].
+/// Use [`decode_lenient`] to also recover from broken image contents.
+pub fn unpack_lenient(file: &[u8]) -> Result<(SogData, Vec<Finding>)> {
+    unpack_lenient_with_limits(file, &Limits::NONE)
+}
+
+/// Same as [`unpack_lenient`], enforcing `limits`. Exceeded limits still fail.
+pub fn unpack_lenient_with_limits(file: &[u8], limits: &Limits) -> Result<(SogData, Vec<Finding>)> {
+    let source = ZipSource::new_with_limits(Cursor::new(file), *limits)?;
+    let meta_json = read_meta_json(&source)?;
+    limits.check_splat_count(&meta_json)?;
+    let error = match parse_sog_with_meta(&source, meta_json.clone()) {
+        Ok(sog_data) => return Ok((sog_data, Vec::new())),
+        Err(e @ ParseError::LimitExceeded(_)) => return Err(e.into()),
+        Err(e) => e,
+    };
+
+    // retry without shN; a new source starts counting decompressed bytes afresh
+    let mut meta_json = meta_json;
+    let Some(object) = meta_json.as_object_mut().filter(|o| o.contains_key("shN")) else {
+        return Err(error.into());
+    };
+    object.remove("shN");
+    let source = ZipSource::new_with_limits(Cursor::new(file), *limits)?;
+    let sog_data = parse_sog_with_meta(&source, meta_json)?;
+    Ok((sog_data, vec![sh_n_dropped(error.into())]))
+}
+
+/// Unpack a bundled `.sog` file from disk.
+/// With the `mmap` feature the file is memory-mapped instead of read through a buffer.
+pub fn unpack_file(path: impl AsRef<Path>) -> Result<SogData> {
+    unpack_file_with_limits(path, &Limits::NONE)
+}
+
+/// Same as [`unpack_file`], enforcing `limits`.
+pub fn unpack_file_with_limits(path: impl AsRef<Path>, limits: &Limits) -> Result<SogData> {
+    let file = File::open(path)?;
+
+    #[cfg(feature = "mmap")]
+    {
+        // SAFETY: the mapping is read-only and dropped before returning.
+        // Modifying the file while it is mapped is undefined behaviour, as with any mmap.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        unpack_reader_with_limits(Cursor::new(&mmap[..]), limits)
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    {
+        unpack_reader_with_limits(BufReader::new(file), limits)
+    }
+}
+
+/// Unpack a bundled `.sog` archive, borrowing stored (uncompressed) images from `file`.
+/// Deflated entries are decompressed into owned buffers. Only version 2 is supported.
+pub fn unpack_ref(file: &[u8]) -> Result<SogDataRef<'_>> {
+    unpack_ref_with_limits(file, &Limits::NONE)
+}
+
+/// Same as [`unpack_ref`], enforcing `limits`. Borrowed images are not decompressed,
+/// so only their dimensions are checked.
+pub fn unpack_ref_with_limits<'a>(file: &'a [u8], limits: &Limits) -> Result<SogDataRef<'a>> {
+    let source = ZipSource::new_with_limits(Cursor::new(file), *limits)?;
+
+    let meta_json = read_meta_json(&source)?;
+    if detect_version(&meta_json)? != Version::V2 {
+        return Err(ParseError::InvalidMetaJson("version is not 2".to_string()).into());
+    }
+    limits.check_splat_count(&meta_json)?;
+    let meta_json =
+        serde_json::from_value::<SogMeta>(meta_json).map_err(ParseError::DeserializeMetaJson)?;
+
+    let sog_data = parse_sog_v2(meta_json, |name| {
+        match source.stored_range(name).and_then(|range| file.get(range)) {
+            Some(data) => {
+                source.check_image(name, data)?;
+                Ok(Cow::Borrowed(data))
+            }
+            None => read_image(&source, name).map(Cow::Owned),
+        }
+    })?;
+    Ok(sog_data)
+}
+
+/// Parse SOG data from `meta.json` and the files it references, keyed by their names.
+/// The images are moved out of `files` without copying, except files referenced more than once.
+pub fn from_files(files: HashMap<String, Vec<u8>>) -> Result<SogData> {
+    from_files_with_limits(files, &Limits::NONE)
+}
+
+/// Same as [`from_files`], enforcing `limits`. The files count as archive entries
+/// and the sizes of the files read as decompressed bytes.
+pub fn from_files_with_limits(files: HashMap<String, Vec<u8>>, limits: &Limits) -> Result<SogData> {
+    Limits::check(
+        Limit::Entries,
+        files.len() as u64,
+        limits.max_entries as u64,
+        None,
+    )
+    .map_err(ParseError::from)?;
+    let source = LimitedSource::new(OwnedFiles::new(files), *limits);
+    let meta_json = read_meta_json(&source)?;
+    limits.check_splat_count(&meta_json)?;
+    source.inner().expect_reads(&meta_json);
+    let sog_data = parse_sog_with_meta(&source, meta_json)?;
+    Ok(sog_data)
+}
+
+/// Load unbundled SOG data: a directory containing `meta.json` and loose WebP files.
+pub fn unpack_dir(path: impl AsRef<Path>) -> Result<SogData> {
+    unpack_dir_with_limits(path, &Limits::NONE)
+}
+
+/// Same as [`unpack_dir`], enforcing `limits`. The sizes of the files read count as
+/// decompressed bytes and are checked before reading them; the directory's other files
+/// are not counted as entries.
+pub fn unpack_dir_with_limits(path: impl AsRef<Path>, limits: &Limits) -> Result<SogData> {
+    unpack_source_with_limits(&DirSource::new(path.as_ref()), limits)
+}
+
+/// Parse SOG data resolving `meta.json` and the files it references from `source`.
+pub fn unpack_source<S: SogSource + ?Sized>(source: &S) -> Result<SogData> {
+    let sog_data = parse_sog(source)?;
+    Ok(sog_data)
+}
+
+/// Same as [`unpack_source`], enforcing `limits`. The sizes of the files read count as
+/// decompressed bytes, checked before reading when [`SogSource::size`] knows them.
+/// Entries are not counted since a source cannot list its files.
+pub fn unpack_source_with_limits<S: SogSource + ?Sized>(
+    source: &S,
+    limits: &Limits,
+) -> Result<SogData> {
+    let source = LimitedSource::new(source, *limits);
+    let meta_json = read_meta_json(&source)?;
+    limits.check_splat_count(&meta_json)?;
+    let sog_data = parse_sog_with_meta(&source, meta_json)?;
+    Ok(sog_data)
+}
+
+/// Destination of a decoded attribute. The length is only known after the image is checked,
+/// so owned output is allocated and borrowed output is size-checked at that point.
+pub(crate) trait Output {
+    fn get(&mut self, len: usize, name: &str) -> DecodeResult<&mut [f32]>;
+}
+
+impl Output for Vec<f32> {
+    fn get(&mut self, len: usize, _name: &str) -> DecodeResult<&mut [f32]> {
+        self.resize(len, 0.0);
+        Ok(self)
+    }
+}
+
+impl Output for &mut [f32] {
+    fn get(&mut self, len: usize, name: &str) -> DecodeResult<&mut [f32]> {
+        if self.len() < len {
+            return Err(DecodeError::InvalidSize(format!(
+                "{} buffer has {} floats, expected at least {}",
+                name,
+                self.len(),
+                len
+            )));
+        }
+        Ok(&mut self[..len])
+    }
+}
+
+pub(crate) fn decode_positions<I: AsRef<[u8]>, O: Output>(
+    means: &Means<I>,
+    count: usize,
+    mut out: O,
+) -> DecodeResult<O> {
     let Means {
         mins,
         maxs,
@@ -157,100 +415,71 @@ fn decode_positions(means: &Means, count: usize) -> DecodeResult<Vec<f32>> {
         means_u,
     } = means;
 
-    let cursor = Cursor::new(means_l);
-    let mut decoder = WebPDecoder::new(cursor)?;
-    let output_size = decoder.output_buffer_size().ok_or_else(|| {
-        DecodeError::InvalidSize("Failed to get output buffer size of WebP image".to_string())
-    })?;
-    let mut lower_pixels = vec![0u8; output_size];
-    decoder.read_image(&mut lower_pixels)?;
-
-    let cursor = Cursor::new(means_u);
-    let mut decoder = WebPDecoder::new(cursor)?;
-    let output_size = decoder.output_buffer_size().ok_or_else(|| {
-        DecodeError::InvalidSize("Failed to get output buffer size of WebP image".to_string())
-    })?;
-    let mut upper_pixels = vec![0u8; output_size];
-    decoder.read_image(&mut upper_pixels)?;
-
-    // sanitize
-    if lower_pixels.len() != upper_pixels.len() {
-        return Err(DecodeError::InvalidSize(
-            "Lower and upper pixels have different length".to_string(),
-        ));
-    } else if lower_pixels.len() % 4 != 0 {
-        return Err(DecodeError::InvalidSize(format!(
-            "lower image size cannot be divided by 4: {}",
-            lower_pixels.len()
-        )));
-    } else if upper_pixels.len() % 4 != 0 {
-        return Err(DecodeError::InvalidSize(format!(
-            "upper image size cannot be divided by 4: {}",
-            upper_pixels.len()
-        )));
-    }
-
-    let mut positions = vec![0f32; count * 3];
-    for i in 0..count {
-        let pos_x = ((upper_pixels[i * 4 + 0] as u16) << 8) | (lower_pixels[i * 4 + 0] as u16);
-        let pos_y = ((upper_pixels[i * 4 + 1] as u16) << 8) | (lower_pixels[i * 4 + 1] as u16);
-        let pos_z = ((upper_pixels[i * 4 + 2] as u16) << 8) | (lower_pixels[i * 4 + 2] as u16);
+    let (means_l, means_u) = (means_l.as_ref(), means_u.as_ref());
+    let (lower, upper) = join(
+        || decode_webp(means_l, "means_l"),
+        || decode_webp(means_u, "means_u"),
+    );
+    let (lower, upper) = (lower?, upper?);
 
-        fn lerp(a: f32, b: f32, t: f32) -> f32 {
-            a + t * (b - a)
-        }
-        fn unlog(x: f32) -> f32 {
-            f32::signum(x) * (f32::exp(f32::abs(x)) - 1.0)
-        }
+    check_means_dims(&lower, &upper)?;
+    lower.check_pixel_count(count, "means_l")?;
+    let lower_pixels = lower.pixels;
+    let upper_pixels = upper.pixels;
 
-        positions[i * 3 + 0] = unlog(lerp(mins.x, maxs.x, pos_x as f32 / 65535.0));
-        positions[i * 3 + 1] = unlog(lerp(mins.y, maxs.y, pos_y as f32 / 65535.0));
-        positions[i * 3 + 2] = unlog(lerp(mins.z, maxs.z, pos_z as f32 / 65535.0));
-    }
+    let positions = out.get(count * 3, "position")?;
+    let (mins, maxs) = ([mins.x, mins.y, mins.z], [maxs.x, maxs.y, maxs.z]);
+    for_each_block(positions, 3, 64, |first, positions| {
+        let pixels = first * 4..(first + positions.len() / 3) * 4;
+        kernels::positions(
+            &lower_pixels[pixels.clone()],
+            &upper_pixels[pixels],
+            mins,
+            maxs,
+            positions,
+        );
+        Ok(())
+    })?;
 
-    Ok(positions)
+    Ok(out)
 }
 
-/// return: f32(x,y,z,w)
+/// return: f32(w,x,y,z)
+/// With `lenient`, splats with an invalid mode get NaN quaternions for [`recover`] to replace.
 #[allow(clippy::identity_op)]
-fn decode_rotations(quats: &Quats, count: usize) -> DecodeResult<Vec<f32>> {
-    let cursor = Cursor::new(&quats.0);
-    let mut decoder = WebPDecoder::new(cursor)?;
-    let output_size = decoder
-        .output_buffer_size()
-        .ok_or_else(|| DecodeError::InvalidSize("cannot determine output size".to_string()))?;
-    let mut pixels = vec![0u8; output_size];
-    decoder.read_image(&mut pixels)?;
+pub(crate) fn decode_rotations<I: AsRef<[u8]>, O: Output>(
+    quats: &Quats<I>,
+    count: usize,
+    lenient: bool,
+    mut out: O,
+) -> DecodeResult<O> {
+    let image = decode_webp(quats.0.as_ref(), "quats")?;
+    image.check_pixel_count(count, "quats")?;
+    let pixels = image.pixels;
 
     fn to_comp(x: f32) -> f32 {
         (x / 255.0 - 0.5) * 2.0 / f32::sqrt(2.0)
     }
 
-    let mut rotations = vec![0f32; count * 4];
-
-    for i in 0..count {
+    let rotations = out.get(count * 4, "rotation")?;
+    for_each_splat(rotations, 4, |i, rotation| {
         let a = to_comp(pixels[i * 4 + 0] as f32);
         let b = to_comp(pixels[i * 4 + 1] as f32);
         let c = to_comp(pixels[i * 4 + 2] as f32);
         let m = pixels[i * 4 + 3];
 
+        if m < 252 && lenient {
+            rotation.fill(f32::NAN);
+            return Ok(());
+        }
         if m < 252 {
-            return Err(DecodeError::InvalidData(format!(
-                "invalid rotation mode(m<252): {}, index: {}",
-                m, i
-            )));
+            return Err(DecodeError::InvalidRotationMode {
+                splat_index: i,
+                entry: None,
+                mode: m,
+            });
         }
-
-        let mode = match m - 252 {
-            0u8 => Ok(0u8),
-            1u8 => Ok(1u8),
-            2u8 => Ok(2u8),
-            3u8 => Ok(3u8),
-            _ => Err(DecodeError::InvalidData(format!(
-                "invalid rotation mode: {}",
-                pixels[i * 4 + 3] - 252
-            ))),
-        }?;
+        let mode = m - 252;
         let d = f32::sqrt(f32::max(0.0, 1.0 - a * a - b * b - c * c));
 
         let q = match mode {
@@ -260,46 +489,41 @@ fn decode_rotations(quats: &Quats, count: usize) -> DecodeResult<Vec<f32>> {
             3 => [a, b, c, d],
             _ => unreachable!(),
         };
-        rotations[i * 4 + 0] = q[0];
-        rotations[i * 4 + 1] = q[1];
-        rotations[i * 4 + 2] = q[2];
-        rotations[i * 4 + 3] = q[3];
-    }
+        rotation.copy_from_slice(&q);
+        Ok(())
+    })?;
 
-    Ok(rotations)
+    Ok(out)
 }
 
 #[allow(clippy::identity_op)]
-fn decode_scales(scales: &Scales, count: usize) -> DecodeResult<Vec<f32>> {
+pub(crate) fn decode_scales<I: AsRef<[u8]>, O: Output>(
+    scales: &Scales<I>,
+    count: usize,
+    mut out: O,
+) -> DecodeResult<O> {
     let Scales { codebook, scales } = scales;
 
-    let cursor = Cursor::new(scales);
-    let mut decoder = WebPDecoder::new(cursor)?;
-    let output_size = decoder
-        .output_buffer_size()
-        .ok_or_else(|| DecodeError::InvalidSize("cannot determine output size".to_string()))?;
-    let mut pixels = vec![0u8; output_size];
-    decoder.read_image(&mut pixels)?;
-
-    if pixels.len() % 4 != 0 {
-        return Err(DecodeError::InvalidData(format!(
-            "scale image size cannot be divided by 4: {}",
-            pixels.len()
-        )));
-    }
+    let image = decode_webp(scales.as_ref(), "scales")?;
+    image.check_pixel_count(count, "scales")?;
+    let pixels = image.pixels;
 
-    let mut scales = vec![0f32; count * 3];
-    for i in 0..count {
-        scales[i * 3 + 0] = codebook.0[pixels[i * 4 + 0] as usize];
-        scales[i * 3 + 1] = codebook.0[pixels[i * 4 + 1] as usize];
-        scales[i * 3 + 2] = codebook.0[pixels[i * 4 + 2] as usize];
-    }
+    let scales = out.get(count * 3, "scale")?;
+    for_each_splat(scales, 3, |i, scale| {
+        scale[0] = codebook.0[pixels[i * 4 + 0] as usize];
+        scale[1] = codebook.0[pixels[i * 4 + 1] as usize];
+        scale[2] = codebook.0[pixels[i * 4 + 2] as usize];
+        Ok(())
+    })?;
 
-    Ok(scales)
+    Ok(out)
 }
 
-#[allow(clippy::identity_op)]
-fn decode_sh_0(sh0: &Sh0, count: usize) -> DecodeResult<Vec<f32>> {
+pub(crate) fn decode_sh_0<I: AsRef<[u8]>, O: Output>(
+    sh0: &Sh0<I>,
+    count: usize,
+    mut out: O,
+) -> DecodeResult<O> {
     // const SH_C0: f32 = 0.28209479177387814; // SH_C0 = Y_0^0 = 1 / (2 * sqrt(pi))
 
     let Sh0 {
@@ -307,110 +531,589 @@ fn decode_sh_0(sh0: &Sh0, count: usize) -> DecodeResult<Vec<f32>> {
         sh_0: sh0,
     } = sh0;
 
-    let cursor = Cursor::new(sh0);
-    let mut decoder = WebPDecoder::new(cursor)?;
-    let output_size = decoder
-        .output_buffer_size()
-        .ok_or_else(|| DecodeError::InvalidSize("cannot determine output size".to_string()))?;
-    let mut pixels = vec![0u8; output_size];
-    decoder.read_image(&mut pixels)?;
-
-    if pixels.len() % 4 != 0 {
-        return Err(DecodeError::InvalidData(format!(
-            "color image size cannot be divided by 4: {}",
-            pixels.len()
-        )));
-    }
-
-    // https://github.com/playcanvas/splat-transform/blob/930a9aec511af3665240589b9cf1727d5dcd2eac/src/lib/readers/read-sog.ts#L174
-    fn sigmoid_inv(y: f32) -> f32 {
-        let e = y.clamp(1e-6, 1.0 - 1e-6);
-        (e / (1.0 - e)).ln()
-    }
+    let image = decode_webp(sh0.as_ref(), "sh0")?;
+    image.check_pixel_count(count, "sh0")?;
+    let pixels = image.pixels;
 
-    let mut colors = vec![0f32; count * 4];
-    for i in 0..count {
-        // colors[i * 4 + 0] = SH_C0 * codebook.0[pixels[i * 4 + 0] as usize] + 0.5;
-        // colors[i * 4 + 1] = SH_C0 * codebook.0[pixels[i * 4 + 1] as usize] + 0.5;
-        // colors[i * 4 + 2] = SH_C0 * codebook.0[pixels[i * 4 + 2] as usize] + 0.5;
-        colors[i * 4 + 0] = codebook.0[pixels[i * 4 + 0] as usize];
-        colors[i * 4 + 1] = codebook.0[pixels[i * 4 + 1] as usize];
-        colors[i * 4 + 2] = codebook.0[pixels[i * 4 + 2] as usize];
-        colors[i * 4 + 3] = sigmoid_inv(pixels[i * 4 + 3] as f32 / 255.0);
-    }
+    let kernel = kernels::Sh0Kernel::new(codebook);
+    let colors = out.get(count * 4, "sh_0")?;
+    for_each_splat(colors, 4, |i, color| {
+        kernel.splat(&pixels[i * 4..i * 4 + 4], color);
+        Ok(())
+    })?;
 
-    Ok(colors)
+    Ok(out)
 }
 
 #[allow(clippy::identity_op)]
-fn decode_sh_n(sh_n: &ShN, count: usize) -> DecodeResult<Vec<f32>> {
+pub(crate) fn decode_sh_n<I: AsRef<[u8]>, O: Output>(
+    sh_n: &ShN<I>,
+    count: usize,
+    max_bands: usize,
+    mut out: O,
+) -> DecodeResult<O> {
     let ShN {
         bands,
         codebook,
         centroids,
         labels,
-        count: _,
+        count: palette_count,
     } = sh_n;
 
-    if *bands <= 0 || *bands >= 4 {
+    let coeff_count = sh_n_coeff_count(*bands)?;
+    // coefficients of bands above the cap are not copied out
+    let out_coeff_count = sh_coeff_count((*bands as usize).min(max_bands));
+    let palette_count = sh_n_palette_count(*palette_count)?;
+
+    // one pixel per coefficient, 64 palette entries per row
+    let (centroids, labels) = (centroids.as_ref(), labels.as_ref());
+    let (centroids, labels) = join(
+        || decode_webp(centroids, "shN_centroids"),
+        || decode_webp(labels, "shN_labels"),
+    );
+    let (centroids, labels) = (centroids?, labels?);
+    centroids.check_pixel_count(
+        sh_n_centroid_pixels(palette_count, coeff_count)?,
+        "shN_centroids",
+    )?;
+    let centroids_pixels = centroids.pixels;
+
+    labels.check_pixel_count(count, "shN_labels")?;
+    let labels_pixels = labels.pixels;
+
+    let sh_n_len = count.checked_mul(out_coeff_count * 3).ok_or_else(|| {
+        DecodeError::InvalidSize(format!("too many shN coefficients for count {}", count))
+    })?;
+    let kernel = kernels::ShNKernel::new(codebook, &centroids_pixels, coeff_count, out_coeff_count);
+    let sh_n_s = out.get(sh_n_len, "sh_n")?;
+    for_each_splat(sh_n_s, out_coeff_count * 3, |splat_index, sh_n| {
+        let palette_index = ((labels_pixels[splat_index * 4 + 0] as u16)
+            | ((labels_pixels[splat_index * 4 + 1] as u16) << 8))
+            as usize;
+
+        if palette_index >= palette_count {
+            return Err(DecodeError::ShNLabelOutOfRange {
+                splat_index,
+                entry: None,
+                label: palette_index,
+                palette_count,
+            });
+        }
+
+        kernel.splat(palette_index, sh_n);
+        Ok(())
+    })?;
+
+    Ok(out)
+}
+
+/// Without `warnings`, pass shN through. Otherwise replace the NaN quaternions
+/// [`decode_rotations`] leaves for invalid modes with the identity and drop shN which failed
+/// to decode, reporting both in `warnings`.
+fn recover(
+    rotation: &mut [f32],
+    sh_n: DecodeResult<Option<Vec<f32>>>,
+    warnings: Option<&mut Vec<Finding>>,
+) -> DecodeResult<Option<Vec<f32>>> {
+    let Some(warnings) = warnings else {
+        return sh_n;
+    };
+
+    let invalid = rotation
+        .chunks_exact_mut(4)
+        .enumerate()
+        .filter(|(_, q)| q[0].is_nan())
+        .map(|(i, q)| {
+            q.copy_from_slice(&[1.0, 0.0, 0.0, 0.0]);
+            i
+        })
+        .collect::<Vec<_>>();
+    if let Some(first) = invalid.first() {
+        warnings.push(Finding {
+            severity: Severity::Warning,
+            code: "invalid_rotation_mode",
+            message: format!(
+                "{} splats have an invalid rotation mode and got the identity rotation",
+                invalid.len()
+            ),
+            location: Location {
+                field: Some("quats".to_string()),
+                splat_index: Some(*first),
+                ..Default::default()
+            },
+        });
+    }
+
+    match sh_n {
+        Ok(sh_n) => Ok(sh_n),
+        Err(e) => {
+            warnings.push(sh_n_dropped(e.into()));
+            Ok(None)
+        }
+    }
+}
+
+fn sh_n_dropped(error: Error) -> Finding {
+    let mut finding = Finding::from_error(Severity::Warning, error);
+    finding.message = format!("shN dropped: {}", finding.message);
+    finding
+}
+
+/// Number of shN coefficients per color channel for `bands` bands.
+/// Saturates for band counts no data can hold, which callers reject.
+pub(crate) fn sh_coeff_count(bands: usize) -> usize {
+    let side = bands.saturating_add(1);
+    side.saturating_mul(side) - 1
+}
+
+/// Number of shN coefficients per color channel of shN data, which holds 1 to 3 bands.
+pub(crate) fn sh_n_coeff_count(bands: i32) -> DecodeResult<usize> {
+    if !(1..=3).contains(&bands) {
         return Err(DecodeError::InvalidSize(format!(
-            "invalid bands count: {}",
+            "shN.bands is {}, expected 1 to 3",
             bands
         )));
     }
+    Ok(sh_coeff_count(bands as usize))
+}
 
-    let cursor = Cursor::new(centroids);
-    let mut decoder = WebPDecoder::new(cursor)?;
-    let output_size = decoder
-        .output_buffer_size()
-        .ok_or_else(|| DecodeError::InvalidSize("cannot determine output size".to_string()))?;
-    let mut centroids_pixels = vec![0u8; output_size];
-    decoder.read_image(&mut centroids_pixels)?;
-
-    let cursor = Cursor::new(labels);
-    let mut decoder = WebPDecoder::new(cursor)?;
-    let output_size = decoder
-        .output_buffer_size()
-        .ok_or_else(|| DecodeError::InvalidSize("cannot determine output size".to_string()))?;
-    let mut labels_pixels = vec![0u8; output_size];
-    decoder.read_image(&mut labels_pixels)?;
-
-    if centroids_pixels.len() % 3 != 0 || labels_pixels.len() % 4 != 0 {
-        return Err(DecodeError::InvalidSize(
-            "invalid image dimensions".to_string(),
-        ));
-    }
-
-    // calc number of coefficients
-    let coeff_count = match bands {
-        1 => 3,
-        2 => 8,
-        3 => 15,
-        _ => Err(DecodeError::InvalidData(format!(
-            "invalid sh bands:{}",
-            bands
-        )))?,
-    };
+/// Palette size from `shN.count`.
+pub(crate) fn sh_n_palette_count(count: i32) -> DecodeResult<usize> {
+    usize::try_from(count)
+        .map_err(|_| DecodeError::InvalidData(format!("invalid shN count: {}", count)))
+}
 
-    let mut sh_n_s = vec![0f32; count * coeff_count * 3];
-    for splat_index in 0..count {
-        let palette_index = ((labels_pixels[splat_index * 4 + 0] as u16)
-            | ((labels_pixels[splat_index * 4 + 1] as u16) << 8))
-            as usize;
+/// Pixels of the shN centroids image: one per coefficient of every palette entry.
+pub(crate) fn sh_n_centroid_pixels(
+    palette_count: usize,
+    coeff_count: usize,
+) -> DecodeResult<usize> {
+    palette_count.checked_mul(coeff_count).ok_or_else(|| {
+        DecodeError::InvalidSize(format!("shN count is too large: {}", palette_count))
+    })
+}
+
+/// Palette size of v1 shN data. v1 has no palette size in meta.json,
+/// so it follows from the pixels of the centroids image.
+pub(crate) fn palette_count_v1(centroid_pixels: usize, coeff_count: usize) -> usize {
+    centroid_pixels / coeff_count
+}
+
+/// Check that means_l and means_u, which hold the two bytes of each position, match.
+pub(crate) fn check_means_dims(lower: &RgbaImage, upper: &RgbaImage) -> DecodeResult<()> {
+    if (lower.width, lower.height) != (upper.width, upper.height) {
+        return Err(DecodeError::InvalidSize(format!(
+            "means_l ({}x{}) and means_u ({}x{}) have different dimensions",
+            lower.width, lower.height, upper.width, upper.height
+        )));
+    }
+    Ok(())
+}
+
+/// Options for [`decode_with`] and [`decode_v2_with`].
+/// Start from [`DecodeOptions::new`] and set options with the `with_*` methods:
+///
+/// ```
+/// # use sog_decoder::{CoordinateSystem, DecodeOptions};
+/// let options = DecodeOptions::new()
+///     .with_sh_n(false)
+///     .with_activate(true)
+///     .with_coordinate_system(CoordinateSystem::default());
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct DecodeOptions {
+    /// Attributes to decode. The images of skipped attributes are not decompressed
+    /// and the corresponding [`Splat`] fields are left empty.
+    pub position: bool,
+    pub rotation: bool,
+    pub scale: bool,
+    pub sh_0: bool,
+    pub sh_n: bool,
+    /// Highest SH degree to decode. Coefficients of higher bands are dropped,
+    /// and shN is skipped entirely for `Some(0)`.
+    pub max_sh_degree: Option<usize>,
+    /// Output render-ready values as with [`Splat::activate`].
+    pub activate: bool,
+    /// Component order of [`Splat::rotation`].
+    pub quaternion_order: QuaternionOrder,
+    /// Normalise the decoded quaternions. They are only approximately unit length otherwise.
+    pub normalize_rotations: bool,
+    /// Also output row-major 3x3 rotation matrices into [`Splat::rotation_matrix`].
+    pub rotation_matrix: bool,
+    /// Also output 3D covariances into [`Splat::covariance`],
+    /// as the upper triangle `xx, xy, xz, yy, yz, zz` of `R * S * S^T * R^T`.
+    /// Rotations and scales are decoded for it even if they are skipped.
+    pub covariance: bool,
+    /// Coordinate system of the output. Positions, rotations, scales and shN are transformed
+    /// from the frame of the SOG data, which is the default.
+    pub coordinate_system: CoordinateSystem,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            position: true,
+            rotation: true,
+            scale: true,
+            sh_0: true,
+            sh_n: true,
+            max_sh_degree: None,
+            activate: false,
+            quaternion_order: QuaternionOrder::Wxyz,
+            normalize_rotations: false,
+            rotation_matrix: false,
+            covariance: false,
+            coordinate_system: CoordinateSystem::default(),
+        }
+    }
+}
+
+impl DecodeOptions {
+    /// Options decoding every attribute as stored, same as [`DecodeOptions::default`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set [`DecodeOptions::position`].
+    pub fn with_position(mut self, position: bool) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Set [`DecodeOptions::rotation`].
+    pub fn with_rotation(mut self, rotation: bool) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Set [`DecodeOptions::scale`].
+    pub fn with_scale(mut self, scale: bool) -> Self {
+        self.scale = scale;
+        self
+    }
 
-        for i in 0..3 {
-            for coeff_index in 0..coeff_count {
-                let index = (splat_index * 3 + i) * coeff_count + coeff_index;
-                let index2 = (palette_index * coeff_count + coeff_index) * 3 + i;
-                sh_n_s[index] = codebook.0[centroids_pixels[index2] as usize];
+    /// Set [`DecodeOptions::sh_0`].
+    pub fn with_sh_0(mut self, sh_0: bool) -> Self {
+        self.sh_0 = sh_0;
+        self
+    }
+
+    /// Set [`DecodeOptions::sh_n`].
+    pub fn with_sh_n(mut self, sh_n: bool) -> Self {
+        self.sh_n = sh_n;
+        self
+    }
+
+    /// Set [`DecodeOptions::max_sh_degree`].
+    pub fn with_max_sh_degree(mut self, max_sh_degree: Option<usize>) -> Self {
+        self.max_sh_degree = max_sh_degree;
+        self
+    }
+
+    /// Set [`DecodeOptions::activate`].
+    pub fn with_activate(mut self, activate: bool) -> Self {
+        self.activate = activate;
+        self
+    }
+
+    /// Set [`DecodeOptions::quaternion_order`].
+    pub fn with_quaternion_order(mut self, quaternion_order: QuaternionOrder) -> Self {
+        self.quaternion_order = quaternion_order;
+        self
+    }
+
+    /// Set [`DecodeOptions::normalize_rotations`].
+    pub fn with_normalize_rotations(mut self, normalize_rotations: bool) -> Self {
+        self.normalize_rotations = normalize_rotations;
+        self
+    }
+
+    /// Set [`DecodeOptions::rotation_matrix`].
+    pub fn with_rotation_matrix(mut self, rotation_matrix: bool) -> Self {
+        self.rotation_matrix = rotation_matrix;
+        self
+    }
+
+    /// Set [`DecodeOptions::covariance`].
+    pub fn with_covariance(mut self, covariance: bool) -> Self {
+        self.covariance = covariance;
+        self
+    }
+
+    /// Set [`DecodeOptions::coordinate_system`].
+    pub fn with_coordinate_system(mut self, coordinate_system: CoordinateSystem) -> Self {
+        self.coordinate_system = coordinate_system;
+        self
+    }
+
+    /// Bands of shN to decode, or `None` if shN is skipped.
+    pub(crate) fn sh_n_bands(&self, bands: usize) -> Option<usize> {
+        match self.max_sh_degree {
+            _ if !self.sh_n => None,
+            Some(0) => None,
+            Some(max) => Some(bands.min(max)),
+            None => Some(bands),
+        }
+    }
+
+    pub(crate) fn decodes_rotation(&self) -> bool {
+        self.rotation || self.rotation_matrix || self.covariance
+    }
+
+    pub(crate) fn decodes_scale(&self) -> bool {
+        self.scale || self.covariance
+    }
+
+    /// Apply the output options to a splat decoded with [`DecodeOptions::decodes_rotation`]
+    /// and [`DecodeOptions::decodes_scale`].
+    pub(crate) fn finish(&self, splat: &mut Splat) -> DecodeResult<()> {
+        if let Some(transform) = Transform::new(&self.coordinate_system)? {
+            transform.positions(&mut splat.position);
+            transform.rotations(&mut splat.rotation);
+            transform.scales(&mut splat.scale);
+            if let Some(sh_n) = &mut splat.sh_n {
+                transform.sh_n(sh_n, sh_coeff_count(splat.sh_degree));
             }
         }
+        if self.normalize_rotations {
+            normalize_quaternions(&mut splat.rotation);
+        }
+        if self.rotation_matrix {
+            splat.rotation_matrix = Some(rotation_matrices(&splat.rotation));
+        }
+        if self.covariance {
+            splat.covariance = Some(covariances(&splat.rotation, &splat.scale));
+        }
+        if !self.rotation {
+            splat.rotation = Vec::new();
+        }
+        if !self.scale {
+            splat.scale = Vec::new();
+        }
+        reorder_quaternions(&mut splat.rotation, self.quaternion_order);
+        if self.activate {
+            splat.activate();
+        }
+        Ok(())
+    }
+}
+
+/// Decode SOG data of any supported version into a [`Splat`].
+pub fn decode(sog_data: &SogData) -> Result<Splat> {
+    decode_with(sog_data, &DecodeOptions::default())
+}
+
+/// Same as [`decode`] with control over the decoded attributes.
+pub fn decode_with(sog_data: &SogData, options: &DecodeOptions) -> Result<Splat> {
+    match sog_data {
+        SogData::V1(sog_data) => decode_v1(sog_data, options, None),
+        SogData::V2(sog_data) => decode_v2_with(sog_data, options),
     }
+}
 
-    Ok(sh_n_s)
+/// Decode SOG v2 data, owned or borrowed, into a [`Splat`].
+pub fn decode_v2<I: AsRef<[u8]>>(sog_data: &SogDataV2<I>) -> Result<Splat> {
+    decode_v2_with(sog_data, &DecodeOptions::default())
 }
 
-pub fn decode(sog_data: &SogDataV2) -> Result<Splat> {
+/// Same as [`decode_v2`] with control over the decoded attributes.
+pub fn decode_v2_with<I: AsRef<[u8]>>(
+    sog_data: &SogDataV2<I>,
+    options: &DecodeOptions,
+) -> Result<Splat> {
+    decode_v2_recovering(sog_data, options, None)
+}
+
+/// Decode SOG data of any version, degrading gracefully instead of failing where possible.
+///
+/// shN which fails to decode is dropped, so `sh_n` is `None` and `sh_degree` is 0, and splats
+/// with an invalid rotation mode get the identity rotation. Every such recovery is reported
+/// as a warning [`Finding`] alongside the splats. Other errors still fail.
+/// Archives whose shN entries or `meta.json` block are broken unpack with [`unpack_lenient`].
+pub fn decode_lenient(
+    sog_data: &SogData,
+    options: &DecodeOptions,
+) -> Result<(Splat, Vec<Finding>)> {
+    let mut warnings = Vec::new();
+    let splat = match sog_data {
+        SogData::V1(sog_data) => decode_v1(sog_data, options, Some(&mut warnings))?,
+        SogData::V2(sog_data) => decode_v2_recovering(sog_data, options, Some(&mut warnings))?,
+    };
+    Ok((splat, warnings))
+}
+
+/// Per-attribute decoders of one SOG version for [`decode_splat`].
+pub(crate) struct AttributeDecoders<P, R, S, C, N> {
+    pub position: P,
+    /// Called with whether decoding is lenient, see [`decode_rotations`].
+    pub rotation: R,
+    pub scale: S,
+    pub sh_0: C,
+    /// Bands of the data and the decoder, called with the bands to output.
+    pub sh_n: Option<(usize, N)>,
+}
+
+/// Decode the attributes `options` asks for and assemble the [`Splat`].
+/// Lenient decoding collects warnings into `warnings`; see [`recover`].
+pub(crate) fn decode_splat<P, R, S, C, N>(
+    decoders: AttributeDecoders<P, R, S, C, N>,
+    count: usize,
+    antialias: bool,
+    names: Option<&EntryNames>,
+    options: &DecodeOptions,
+    warnings: Option<&mut Vec<Finding>>,
+) -> Result<Splat>
+where
+    P: FnOnce() -> DecodeResult<Vec<f32>> + Send,
+    R: FnOnce(bool) -> DecodeResult<Vec<f32>> + Send,
+    S: FnOnce() -> DecodeResult<Vec<f32>> + Send,
+    C: FnOnce() -> DecodeResult<Vec<f32>> + Send,
+    N: FnOnce(usize) -> DecodeResult<Vec<f32>> + Send,
+{
+    let lenient = warnings.is_some();
+    let AttributeDecoders {
+        position,
+        rotation,
+        scale,
+        sh_0,
+        sh_n,
+    } = decoders;
+    let sh_n_bands = sh_n
+        .as_ref()
+        .and_then(|(bands, _)| options.sh_n_bands(*bands));
+
+    // attributes are decoded concurrently with the `rayon` feature
+    let ((position, rotation), (scale, (colors, coeffs))) = join(
+        || {
+            join(
+                || {
+                    if options.position {
+                        position()
+                    } else {
+                        Ok(Vec::new())
+                    }
+                },
+                || {
+                    if options.decodes_rotation() {
+                        rotation(lenient)
+                    } else {
+                        Ok(Vec::new())
+                    }
+                },
+            )
+        },
+        || {
+            join(
+                || {
+                    if options.decodes_scale() {
+                        scale()
+                    } else {
+                        Ok(Vec::new())
+                    }
+                },
+                || {
+                    join(
+                        || {
+                            if options.sh_0 { sh_0() } else { Ok(Vec::new()) }
+                        },
+                        || match (sh_n, sh_n_bands) {
+                            (Some((_, decode)), Some(bands)) => decode(bands).map(Some),
+                            _ => Ok(None),
+                        },
+                    )
+                },
+            )
+        },
+    );
+
+    let in_entry = |e: DecodeError| e.in_entry(names);
+    let mut rotation = rotation.map_err(in_entry)?;
+    let coeffs = recover(&mut rotation, coeffs.map_err(in_entry), warnings)?;
+    let mut splat = Splat {
+        position: position.map_err(in_entry)?,
+        rotation,
+        scale: scale.map_err(in_entry)?,
+        sh_0: colors.map_err(in_entry)?,
+        sh_degree: coeffs.as_ref().and(sh_n_bands).unwrap_or(0),
+        sh_n: coeffs,
+        count,
+        antialias,
+        rotation_matrix: None,
+        covariance: None,
+    };
+    options.finish(&mut splat)?;
+
+    Ok(splat)
+}
+
+fn decode_v2_recovering<I: AsRef<[u8]>>(
+    sog_data: &SogDataV2<I>,
+    options: &DecodeOptions,
+    warnings: Option<&mut Vec<Finding>>,
+) -> Result<Splat> {
+    let count = sog_data.count as usize;
+
+    // borrowed images can be shared across threads whatever `I` is
+    let borrowed = sog_data.as_borrowed();
+    let SogDataV2 {
+        means,
+        quats,
+        scales,
+        sh_0,
+        sh_n,
+        ..
+    } = &borrowed;
+
+    let decoders = AttributeDecoders {
+        position: || decode_positions(means, count, Vec::new()),
+        rotation: |lenient| decode_rotations(quats, count, lenient, Vec::new()),
+        scale: || decode_scales(scales, count, Vec::new()),
+        sh_0: || decode_sh_0(sh_0, count, Vec::new()),
+        sh_n: sh_n.as_ref().map(|sh_n| {
+            let decode = move |bands| decode_sh_n(sh_n, count, bands, Vec::new());
+            (sh_n.bands as usize, decode)
+        }),
+    };
+    decode_splat(
+        decoders,
+        count,
+        sog_data.antialias,
+        sog_data.names.as_ref(),
+        options,
+        warnings,
+    )
+}
+
+/// Caller-provided output slices for [`decode_into`], laid out like the fields of [`Splat`].
+/// Attributes without a slice are skipped. Slices may be longer than needed;
+/// only the leading part is written.
+#[derive(Debug, Default)]
+pub struct SplatBuffers<'a> {
+    pub position: Option<&'a mut [f32]>,
+    pub rotation: Option<&'a mut [f32]>,
+    pub scale: Option<&'a mut [f32]>,
+    pub sh_0: Option<&'a mut [f32]>,
+    pub sh_n: Option<&'a mut [f32]>,
+}
+
+/// Decode SOG v2 data into caller-provided slices instead of allocating a [`Splat`].
+/// Returns an error if a slice is too small for its attribute.
+pub fn decode_into<I: AsRef<[u8]>>(
+    sog_data: &SogDataV2<I>,
+    buffers: &mut SplatBuffers,
+) -> Result<()> {
+    decode_into_with(sog_data, buffers, &DecodeOptions::default())
+}
+
+/// Same as [`decode_into`] with control over the decoded attributes.
+/// An attribute is decoded if it is enabled in the options and has a slice.
+/// Rotation matrices and covariances are not output.
+pub fn decode_into_with<I: AsRef<[u8]>>(
+    sog_data: &SogDataV2<I>,
+    buffers: &mut SplatBuffers,
+    options: &DecodeOptions,
+) -> Result<()> {
     let SogDataV2 {
         means,
         quats,
@@ -421,21 +1124,83 @@ pub fn decode(sog_data: &SogDataV2) -> Result<Splat> {
     } = sog_data;
 
     let count = sog_data.count as usize;
+    let transform = Transform::new(&options.coordinate_system)?;
+    let in_entry = |e: DecodeError| e.in_entry(sog_data.names.as_ref());
 
-    let splat = Splat {
-        position: decode_positions(means, count)?,
-        rotation: decode_rotations(quats, count)?,
-        scale: decode_scales(scales, count)?,
-        sh_0: decode_sh_0(sh_0, count)?,
-        sh_n: if let Some(s) = sh_n {
-            Some(decode_sh_n(s, count)?)
-        } else {
-            None
-        },
-        count,
-        antialias: sog_data.antialias,
-        sh_degree: sh_n.as_ref().map(|s| s.bands as usize).unwrap_or(0usize),
-    };
+    if let (true, Some(out)) = (options.position, buffers.position.as_deref_mut()) {
+        let out = decode_positions(means, count, out).map_err(in_entry)?;
+        if let Some(transform) = &transform {
+            transform.positions(&mut out[..count * 3]);
+        }
+    }
+    if let (true, Some(out)) = (options.rotation, buffers.rotation.as_deref_mut()) {
+        let out = decode_rotations(quats, count, false, out).map_err(in_entry)?;
+        if let Some(transform) = &transform {
+            transform.rotations(&mut out[..count * 4]);
+        }
+        if options.normalize_rotations {
+            normalize_quaternions(&mut out[..count * 4]);
+        }
+        reorder_quaternions(&mut out[..count * 4], options.quaternion_order);
+    }
+    if let (true, Some(out)) = (options.scale, buffers.scale.as_deref_mut()) {
+        let out = decode_scales(scales, count, out).map_err(in_entry)?;
+        if let Some(transform) = &transform {
+            transform.scales(&mut out[..count * 3]);
+        }
+        if options.activate {
+            activate_scales(&mut out[..count * 3]);
+        }
+    }
+    if let (true, Some(out)) = (options.sh_0, buffers.sh_0.as_deref_mut()) {
+        let out = decode_sh_0(sh_0, count, out).map_err(in_entry)?;
+        if options.activate {
+            activate_colors(&mut out[..count * 4]);
+        }
+    }
+    if let (Some(sh_n), Some(out)) = (sh_n, buffers.sh_n.as_deref_mut())
+        && let Some(bands) = options.sh_n_bands(sh_n.bands as usize)
+    {
+        let out = decode_sh_n(sh_n, count, bands, out).map_err(in_entry)?;
+        if let Some(transform) = &transform {
+            let coeff_count = sh_coeff_count(bands.min(sh_n.bands as usize));
+            transform.sh_n(&mut out[..count * coeff_count * 3], coeff_count);
+        }
+    }
 
-    Ok(splat)
+    Ok(())
+}
+
+/// Decode the WebP images to RGBA8 without dequantizing them.
+/// The image sizes are checked against the splat count as in [`decode_v2`].
+pub fn decode_quantized<I: AsRef<[u8]>>(sog_data: &SogDataV2<I>) -> Result<QuantizedSogData> {
+    let in_entry = |e: DecodeError| e.in_entry(sog_data.names.as_ref());
+    let quantized = sog_data
+        .as_borrowed()
+        .try_map_images(|name, image| decode_webp(&image, name))
+        .map_err(in_entry)?;
+    check_quantized(&quantized).map_err(in_entry)?;
+    Ok(quantized)
+}
+
+/// Check the image sizes of [`decode_quantized`] output against the splat count.
+fn check_quantized(quantized: &QuantizedSogData) -> DecodeResult<()> {
+    let count = quantized.count as usize;
+    let (lower, upper) = (&quantized.means.means_l, &quantized.means.means_u);
+    check_means_dims(lower, upper)?;
+    lower.check_pixel_count(count, "means_l")?;
+    quantized.scales.scales.check_pixel_count(count, "scales")?;
+    quantized.quats.0.check_pixel_count(count, "quats")?;
+    quantized.sh_0.sh_0.check_pixel_count(count, "sh0")?;
+    if let Some(sh_n) = &quantized.sh_n {
+        let coeff_count = sh_n_coeff_count(sh_n.bands)?;
+        let palette_count = sh_n_palette_count(sh_n.count)?;
+        sh_n.centroids.check_pixel_count(
+            sh_n_centroid_pixels(palette_count, coeff_count)?,
+            "shN_centroids",
+        )?;
+        sh_n.labels.check_pixel_count(count, "shN_labels")?;
+    }
+
+    Ok(())
 }
@@ -0,0 +1,734 @@
+use crate::error::{EncodeError, EncodeResult, Result};
+use crate::parallel::{for_each_block_with, map_splats};
+use crate::types::{Codebook, Means, Quats, Scales, Sh0, ShN, SogDataV2, Splat, Vector3};
+use image_webp::{ColorType, WebPEncoder};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Options for [`encode`].
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    /// Maximum number of entries in the shN palette.
+    /// Clamped to the splat count and 65536.
+    pub sh_palette_size: usize,
+    /// Number of k-means iterations used to fit the codebooks and the shN palette.
+    pub iterations: usize,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        Self {
+            sh_palette_size: 4096,
+            iterations: 10,
+        }
+    }
+}
+
+/// Size of the image which stores one pixel per splat.
+fn image_size(count: usize) -> (u32, u32) {
+    let width = (f64::sqrt(count as f64) / 4.0).ceil().max(1.0) as usize * 4;
+    let height = count.div_ceil(width).div_ceil(4).max(1) * 4;
+    (width as u32, height as u32)
+}
+
+fn encode_webp(pixels: &[u8], width: u32, height: u32, color: ColorType) -> EncodeResult<Vec<u8>> {
+    let mut buf = Vec::new();
+    WebPEncoder::new(&mut buf).encode(pixels, width, height, color)?;
+    Ok(buf)
+}
+
+fn sh_coeff_count(bands: usize) -> EncodeResult<usize> {
+    match bands {
+        1 => Ok(3),
+        2 => Ok(8),
+        3 => Ok(15),
+        _ => Err(EncodeError::InvalidData(format!(
+            "invalid sh bands:{}",
+            bands
+        ))),
+    }
+}
+
+/// Fit a sorted 256-entry codebook to `values` with 1D k-means.
+/// Up to 256 distinct values are reproduced exactly.
+fn fit_codebook(values: &[f32], iterations: usize) -> Codebook {
+    let mut sorted = values
+        .iter()
+        .copied()
+        .filter(|v| v.is_finite())
+        .collect::<Vec<_>>();
+    if sorted.is_empty() {
+        return Codebook([0.0; 256]);
+    }
+    sorted.sort_by(f32::total_cmp);
+
+    // k-means runs on the distinct values weighted by how often they occur,
+    // so repeated values cannot seed duplicate centroids
+    let mut distinct = Vec::<f32>::new();
+    let mut weights = Vec::<f64>::new();
+    for v in sorted {
+        if distinct.last() == Some(&v) {
+            *weights.last_mut().unwrap() += 1.0;
+        } else {
+            distinct.push(v);
+            weights.push(1.0);
+        }
+    }
+
+    let n = distinct.len();
+    let mut centroids = [0f32; 256];
+    if n <= 256 {
+        // the remaining entries repeat the largest value, which keeps the codebook sorted
+        for (k, c) in centroids.iter_mut().enumerate() {
+            *c = distinct[k.min(n - 1)];
+        }
+        return Codebook(centroids);
+    }
+    for (k, c) in centroids.iter_mut().enumerate() {
+        *c = distinct[(2 * k + 1) * n / 512];
+    }
+
+    // prefix sums let every cluster mean be computed from a range of the sorted values
+    let mut weight_prefix = vec![0f64; n + 1];
+    let mut value_prefix = vec![0f64; n + 1];
+    for i in 0..n {
+        weight_prefix[i + 1] = weight_prefix[i] + weights[i];
+        value_prefix[i + 1] = value_prefix[i] + weights[i] * distinct[i] as f64;
+    }
+
+    for _ in 0..iterations {
+        let mut ranges = [0usize; 257];
+        let mut empty = Vec::new();
+        let mut changed = false;
+        for k in 0..256 {
+            let start = ranges[k];
+            let end = if k == 255 {
+                n
+            } else {
+                let boundary = (centroids[k] + centroids[k + 1]) * 0.5;
+                start + distinct[start..].partition_point(|v| *v <= boundary)
+            };
+            ranges[k + 1] = end;
+            if end > start {
+                let weight = weight_prefix[end] - weight_prefix[start];
+                let mean = ((value_prefix[end] - value_prefix[start]) / weight) as f32;
+                changed |= mean != centroids[k];
+                centroids[k] = mean;
+            } else {
+                empty.push(k);
+            }
+        }
+
+        if !empty.is_empty() {
+            // empty clusters are re-seeded with the values fitted worst
+            let mut errors = (0..256)
+                .flat_map(|k| (ranges[k]..ranges[k + 1]).map(move |i| (k, i)))
+                .map(|(k, i)| {
+                    let d = (distinct[i] - centroids[k]) as f64;
+                    (weights[i] * d * d, i)
+                })
+                .collect::<Vec<_>>();
+            errors.sort_by(|a, b| b.0.total_cmp(&a.0));
+            for (k, (_, i)) in empty.into_iter().zip(errors) {
+                centroids[k] = distinct[i];
+            }
+            centroids.sort_by(f32::total_cmp);
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    Codebook(centroids)
+}
+
+/// Index of the codebook entry nearest to `value`.
+fn quantize(codebook: &Codebook, value: f32) -> u8 {
+    let entries = &codebook.0;
+    let i = entries.partition_point(|c| *c < value);
+    if i == 0 {
+        0
+    } else if i == entries.len() || value - entries[i - 1] <= entries[i] - value {
+        (i - 1) as u8
+    } else {
+        i as u8
+    }
+}
+
+#[allow(clippy::identity_op)]
+fn encode_positions(position: &[f32], count: usize) -> EncodeResult<Means> {
+    fn log(x: f32) -> f32 {
+        f32::signum(x) * f32::ln(f32::abs(x) + 1.0)
+    }
+
+    let logs = position.iter().map(|v| log(*v)).collect::<Vec<_>>();
+
+    let mut mins = [f32::INFINITY; 3];
+    let mut maxs = [f32::NEG_INFINITY; 3];
+    for p in logs.chunks_exact(3) {
+        for axis in 0..3 {
+            mins[axis] = mins[axis].min(p[axis]);
+            maxs[axis] = maxs[axis].max(p[axis]);
+        }
+    }
+
+    let (width, height) = image_size(count);
+    let mut lower_pixels = vec![0u8; width as usize * height as usize * 4];
+    let mut upper_pixels = vec![0u8; width as usize * height as usize * 4];
+    for (i, p) in logs.chunks_exact(3).enumerate() {
+        for axis in 0..3 {
+            let range = maxs[axis] - mins[axis];
+            let t = if range > 0.0 {
+                (p[axis] - mins[axis]) / range
+            } else {
+                0.0
+            };
+            let q = (t * 65535.0).round() as u16;
+            lower_pixels[i * 4 + axis] = (q & 0xff) as u8;
+            upper_pixels[i * 4 + axis] = (q >> 8) as u8;
+        }
+        lower_pixels[i * 4 + 3] = 255;
+        upper_pixels[i * 4 + 3] = 255;
+    }
+
+    Ok(Means {
+        mins: Vector3::new(mins[0], mins[1], mins[2]),
+        maxs: Vector3::new(maxs[0], maxs[1], maxs[2]),
+        means_l: encode_webp(&lower_pixels, width, height, ColorType::Rgba8)?,
+        means_u: encode_webp(&upper_pixels, width, height, ColorType::Rgba8)?,
+    })
+}
+
+/// Pack quaternions smallest-three style: the largest component is dropped and
+/// its index stored in alpha as `252 + index`.
+#[allow(clippy::identity_op)]
+fn encode_rotations(rotation: &[f32], count: usize) -> EncodeResult<Quats> {
+    fn from_comp(x: f32) -> u8 {
+        ((x * f32::sqrt(2.0) / 2.0 + 0.5) * 255.0)
+            .round()
+            .clamp(0.0, 255.0) as u8
+    }
+
+    let (width, height) = image_size(count);
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    for (i, q) in rotation.chunks_exact(4).enumerate() {
+        let length = f32::sqrt(q.iter().map(|c| c * c).sum());
+        let mut q = if length > 0.0 {
+            [q[0] / length, q[1] / length, q[2] / length, q[3] / length]
+        } else {
+            [1.0, 0.0, 0.0, 0.0]
+        };
+
+        let mut mode = 0;
+        for k in 1..4 {
+            if q[k].abs() > q[mode].abs() {
+                mode = k;
+            }
+        }
+        // q and -q are the same rotation; the dropped component must be positive
+        if q[mode] < 0.0 {
+            q.iter_mut().for_each(|c| *c = -*c);
+        }
+
+        let mut k = 0;
+        for (j, c) in q.iter().enumerate() {
+            if j != mode {
+                pixels[i * 4 + k] = from_comp(*c);
+                k += 1;
+            }
+        }
+        pixels[i * 4 + 3] = 252 + mode as u8;
+    }
+
+    Ok(Quats(encode_webp(
+        &pixels,
+        width,
+        height,
+        ColorType::Rgba8,
+    )?))
+}
+
+#[allow(clippy::identity_op)]
+fn encode_scales(scale: &[f32], count: usize, iterations: usize) -> EncodeResult<Scales> {
+    let codebook = fit_codebook(scale, iterations);
+
+    let (width, height) = image_size(count);
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    for (i, s) in scale.chunks_exact(3).enumerate() {
+        pixels[i * 4 + 0] = quantize(&codebook, s[0]);
+        pixels[i * 4 + 1] = quantize(&codebook, s[1]);
+        pixels[i * 4 + 2] = quantize(&codebook, s[2]);
+        pixels[i * 4 + 3] = 255;
+    }
+
+    Ok(Scales {
+        scales: encode_webp(&pixels, width, height, ColorType::Rgba8)?,
+        codebook,
+    })
+}
+
+#[allow(clippy::identity_op)]
+fn encode_sh_0(sh_0: &[f32], count: usize, iterations: usize) -> EncodeResult<Sh0> {
+    let colors = sh_0
+        .chunks_exact(4)
+        .flat_map(|c| [c[0], c[1], c[2]])
+        .collect::<Vec<_>>();
+    let codebook = fit_codebook(&colors, iterations);
+
+    fn sigmoid(x: f32) -> f32 {
+        1.0 / (1.0 + f32::exp(-x))
+    }
+
+    let (width, height) = image_size(count);
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    for (i, c) in sh_0.chunks_exact(4).enumerate() {
+        pixels[i * 4 + 0] = quantize(&codebook, c[0]);
+        pixels[i * 4 + 1] = quantize(&codebook, c[1]);
+        pixels[i * 4 + 2] = quantize(&codebook, c[2]);
+        pixels[i * 4 + 3] = (sigmoid(c[3]) * 255.0).round() as u8;
+    }
+
+    Ok(Sh0 {
+        sh_0: encode_webp(&pixels, width, height, ColorType::Rgba8)?,
+        codebook,
+    })
+}
+
+fn squared_distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum()
+}
+
+/// Centroids per tile of [`Centroids`].
+const TILE: usize = 16;
+
+/// Groups of centroids with a lower bound each in [`Assignment`].
+const GROUPS: usize = 16;
+
+/// Palette centroids sorted by group and stored in tiles of `TILE` with one row per dimension,
+/// so that the distances from a point to a tile accumulate in registers and vectorise.
+struct Centroids<'a> {
+    dims: usize,
+    centroids: &'a [f32],
+    tiles: Vec<[f32; TILE]>,
+    /// Squared norm of every centroid, infinite for the padding at the end of a group.
+    norms: Vec<[f32; TILE]>,
+    /// Centroid index of every lane of the tiles.
+    indices: Vec<[usize; TILE]>,
+    /// Tiles of every group.
+    groups: Vec<Range<usize>>,
+}
+
+impl<'a> Centroids<'a> {
+    fn new(centroids: &'a [f32], dims: usize, groups: &[Vec<usize>]) -> Self {
+        let mut tiles = Vec::new();
+        let mut norms = Vec::new();
+        let mut indices = Vec::new();
+        let mut ranges = Vec::with_capacity(groups.len());
+        for members in groups {
+            let start = norms.len();
+            for chunk in members.chunks(TILE) {
+                let mut tile = vec![[0f32; TILE]; dims];
+                let mut tile_norms = [f32::INFINITY; TILE];
+                let mut tile_indices = [usize::MAX; TILE];
+                for (lane, j) in chunk.iter().enumerate() {
+                    let centroid = &centroids[j * dims..(j + 1) * dims];
+                    for (row, v) in tile.iter_mut().zip(centroid) {
+                        row[lane] = *v;
+                    }
+                    tile_norms[lane] = centroid.iter().map(|v| v * v).sum();
+                    tile_indices[lane] = *j;
+                }
+                tiles.extend(tile);
+                norms.push(tile_norms);
+                indices.push(tile_indices);
+            }
+            ranges.push(start..norms.len());
+        }
+        Self {
+            dims,
+            centroids,
+            tiles,
+            norms,
+            indices,
+            groups: ranges,
+        }
+    }
+
+    fn distance(&self, point: &[f32], j: usize) -> f32 {
+        squared_distance(point, &self.centroids[j * self.dims..(j + 1) * self.dims]).sqrt()
+    }
+
+    /// Nearest and second nearest centroid of `point` in a group, with their distances.
+    fn nearest_two(&self, point: &[f32], group: usize) -> ((usize, f32), f32) {
+        // |p - c|^2 = |p|^2 + |c|^2 - 2 p.c
+        let norm = point.iter().map(|v| v * v).sum::<f32>();
+        let (mut first, mut second) = ((usize::MAX, f32::INFINITY), f32::INFINITY);
+        for t in self.groups[group].clone() {
+            let mut dots = [0f32; TILE];
+            for (row, v) in self.tiles[t * self.dims..(t + 1) * self.dims]
+                .iter()
+                .zip(point)
+            {
+                for (dot, c) in dots.iter_mut().zip(row) {
+                    *dot += v * c;
+                }
+            }
+            for ((dot, c), j) in dots.iter().zip(&self.norms[t]).zip(&self.indices[t]) {
+                let distance = norm + c - 2.0 * dot;
+                if distance < first.1 {
+                    second = first.1;
+                    first = (*j, distance);
+                } else if distance < second {
+                    second = distance;
+                }
+            }
+        }
+        ((first.0, first.1.max(0.0).sqrt()), second.max(0.0).sqrt())
+    }
+
+    fn nearest(&self, point: &[f32]) -> usize {
+        (0..self.groups.len())
+            .map(|g| self.nearest_two(point, g).0)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map_or(0, |(j, _)| j)
+    }
+}
+
+/// Label of a point with the bounds of Yinyang k-means: `upper` is at least the distance to
+/// the centroid of `label` and `lower[g]` at most the distance to any other centroid of
+/// group `g`, so only the groups whose bound is below `upper` can hold a nearer centroid.
+#[derive(Debug, Clone, Copy)]
+struct Assignment {
+    label: u16,
+    upper: f32,
+    lower: [f32; GROUPS],
+}
+
+impl Assignment {
+    const NONE: Self = Self {
+        label: 0,
+        upper: f32::INFINITY,
+        lower: [0.0; GROUPS],
+    };
+}
+
+/// Assign every point to its nearest centroid, searching only the groups the bounds allow.
+fn assign(
+    points: &[f32],
+    centroids: &Centroids,
+    group_of: &[usize],
+    assignments: &mut [Assignment],
+) {
+    const POINTS_PER_TASK: usize = 64;
+
+    let dims = centroids.dims;
+    let group_count = centroids.groups.len();
+    for_each_block_with(
+        points,
+        dims,
+        assignments,
+        POINTS_PER_TASK,
+        |points, assignments| {
+            for (point, assignment) in points.chunks_exact(dims).zip(assignments) {
+                let lower = &mut assignment.lower[..group_count];
+                let global = lower.iter().copied().fold(f32::INFINITY, f32::min);
+                if assignment.upper <= global {
+                    continue;
+                }
+                let previous = assignment.label as usize;
+                assignment.upper = centroids.distance(point, previous);
+                if assignment.upper <= global {
+                    continue;
+                }
+
+                let mut scanned = [None; GROUPS];
+                let mut best = (previous, assignment.upper);
+                for g in 0..group_count {
+                    if lower[g] < best.1 {
+                        let nearest_two = centroids.nearest_two(point, g);
+                        if nearest_two.0.1 < best.1 {
+                            best = nearest_two.0;
+                        }
+                        scanned[g] = Some(nearest_two);
+                    }
+                }
+
+                // the bound of a group is its nearest centroid other than the label
+                for (g, scanned) in scanned.iter().enumerate().take(group_count) {
+                    if let Some(((nearest, distance), second)) = *scanned {
+                        lower[g] = if nearest == best.0 { second } else { distance };
+                    }
+                }
+                if best.0 != previous && scanned[group_of[previous]].is_none() {
+                    let g = group_of[previous];
+                    lower[g] = lower[g].min(assignment.upper);
+                }
+                assignment.label = best.0 as u16;
+                assignment.upper = best.1;
+            }
+        },
+    );
+}
+
+/// Split the centroids into up to `GROUPS` groups of nearby ones with a few k-means iterations.
+fn group_centroids(centroids: &[f32], dims: usize) -> Vec<usize> {
+    let k = centroids.len() / dims;
+    let group_count = GROUPS.min(k);
+    let mut centers = (0..group_count)
+        .flat_map(|g| {
+            let j = g * k / group_count;
+            &centroids[j * dims..(j + 1) * dims]
+        })
+        .copied()
+        .collect::<Vec<_>>();
+
+    let mut group_of = vec![0; k];
+    for _ in 0..5 {
+        for (group, centroid) in group_of.iter_mut().zip(centroids.chunks_exact(dims)) {
+            *group = (0..group_count)
+                .min_by(|a, b| {
+                    let a = squared_distance(centroid, &centers[a * dims..(a + 1) * dims]);
+                    let b = squared_distance(centroid, &centers[b * dims..(b + 1) * dims]);
+                    a.total_cmp(&b)
+                })
+                .unwrap_or(0);
+        }
+        let mut sums = vec![0f32; group_count * dims];
+        let mut sizes = vec![0usize; group_count];
+        for (group, centroid) in group_of.iter().zip(centroids.chunks_exact(dims)) {
+            sizes[*group] += 1;
+            for (sum, v) in sums[group * dims..(group + 1) * dims]
+                .iter_mut()
+                .zip(centroid)
+            {
+                *sum += v;
+            }
+        }
+        for g in 0..group_count {
+            if sizes[g] > 0 {
+                for d in 0..dims {
+                    centers[g * dims + d] = sums[g * dims + d] / sizes[g] as f32;
+                }
+            }
+        }
+    }
+    group_of
+}
+
+/// Cluster the per-splat shN vectors into a palette with k-means.
+/// Returns the palette (`k * dims` values) and the label of every splat.
+///
+/// The centroids are fitted to an evenly spaced sample of at most `SAMPLES_PER_ENTRY` splats
+/// per palette entry, and only the final labels are assigned for every splat.
+fn fit_palette(
+    sh_n: &[f32],
+    count: usize,
+    dims: usize,
+    palette_size: usize,
+    iterations: usize,
+) -> (Vec<f32>, Vec<u16>) {
+    const SAMPLES_PER_ENTRY: usize = 8;
+
+    let k = palette_size.clamp(1, 65536).min(count);
+
+    let sample_count = count.min(k * SAMPLES_PER_ENTRY);
+    let sample = if sample_count == count {
+        Cow::Borrowed(&sh_n[..count * dims])
+    } else {
+        Cow::Owned(
+            (0..sample_count)
+                .flat_map(|i| {
+                    let splat_index = i * count / sample_count;
+                    &sh_n[splat_index * dims..(splat_index + 1) * dims]
+                })
+                .copied()
+                .collect(),
+        )
+    };
+
+    let mut centroids = Vec::with_capacity(k * dims);
+    for i in 0..k {
+        let splat_index = i * sample_count / k;
+        centroids.extend_from_slice(&sample[splat_index * dims..(splat_index + 1) * dims]);
+    }
+
+    // centroids stay in the groups of their initial positions
+    let group_of = group_centroids(&centroids, dims);
+    let mut groups = vec![Vec::new(); GROUPS.min(k)];
+    for (j, group) in group_of.iter().enumerate() {
+        groups[*group].push(j);
+    }
+
+    let mut assignments = vec![Assignment::NONE; sample_count];
+    for _ in 0..iterations {
+        assign(
+            &sample,
+            &Centroids::new(&centroids, dims, &groups),
+            &group_of,
+            &mut assignments,
+        );
+
+        let mut sums = vec![0f64; k * dims];
+        let mut sizes = vec![0usize; k];
+        for (assignment, point) in assignments.iter().zip(sample.chunks_exact(dims)) {
+            let label = assignment.label as usize;
+            sizes[label] += 1;
+            for (sum, v) in sums[label * dims..(label + 1) * dims].iter_mut().zip(point) {
+                *sum += *v as f64;
+            }
+        }
+        let mut shifts = vec![0f32; k];
+        for label in 0..k {
+            // empty clusters keep their previous centroid
+            if sizes[label] == 0 {
+                continue;
+            }
+            let centroid = &mut centroids[label * dims..(label + 1) * dims];
+            let previous = centroid.to_vec();
+            for (d, c) in centroid.iter_mut().enumerate() {
+                *c = (sums[label * dims + d] / sizes[label] as f64) as f32;
+            }
+            shifts[label] = squared_distance(&previous, centroid).sqrt();
+        }
+
+        // moving centroids loosen the bounds by as much as they moved
+        let mut group_shifts = [0f32; GROUPS];
+        for (shift, group) in shifts.iter().zip(&group_of) {
+            group_shifts[*group] = group_shifts[*group].max(*shift);
+        }
+        for assignment in &mut assignments {
+            assignment.upper += shifts[assignment.label as usize];
+            for (lower, shift) in assignment.lower.iter_mut().zip(&group_shifts) {
+                *lower -= shift;
+            }
+        }
+    }
+
+    let centroids_index = Centroids::new(&centroids, dims, &groups);
+    let labels = if sample_count == count {
+        assign(sh_n, &centroids_index, &group_of, &mut assignments);
+        assignments.iter().map(|a| a.label).collect()
+    } else {
+        let mut labels = vec![0u16; count];
+        map_splats(sh_n, dims, &mut labels, |point| {
+            centroids_index.nearest(point) as u16
+        });
+        labels
+    };
+    (centroids, labels)
+}
+
+fn encode_sh_n(
+    sh_n: &[f32],
+    count: usize,
+    bands: usize,
+    options: &EncodeOptions,
+) -> EncodeResult<ShN> {
+    let coeff_count = sh_coeff_count(bands)?;
+    let dims = coeff_count * 3;
+
+    let (palette, labels) = fit_palette(
+        sh_n,
+        count,
+        dims,
+        options.sh_palette_size,
+        options.iterations,
+    );
+    let palette_count = palette.len() / dims;
+    let codebook = fit_codebook(&palette, options.iterations);
+
+    // centroids: 64 palette entries per row, one pixel per coefficient, rgb per channel
+    let width = 64 * coeff_count;
+    let height = palette_count.div_ceil(64);
+    let mut centroids_pixels = vec![0u8; width * height * 3];
+    for (palette_index, centroid) in palette.chunks_exact(dims).enumerate() {
+        for i in 0..3 {
+            for coeff_index in 0..coeff_count {
+                let index = (palette_index * coeff_count + coeff_index) * 3 + i;
+                centroids_pixels[index] =
+                    quantize(&codebook, centroid[i * coeff_count + coeff_index]);
+            }
+        }
+    }
+
+    let (label_width, label_height) = image_size(count);
+    let mut labels_pixels = vec![0u8; label_width as usize * label_height as usize * 4];
+    for (i, label) in labels.iter().enumerate() {
+        labels_pixels[i * 4] = (label & 0xff) as u8;
+        labels_pixels[i * 4 + 1] = (label >> 8) as u8;
+        labels_pixels[i * 4 + 3] = 255;
+    }
+
+    Ok(ShN {
+        count: palette_count as i32,
+        bands: bands as i32,
+        codebook,
+        centroids: encode_webp(
+            &centroids_pixels,
+            width as u32,
+            height as u32,
+            ColorType::Rgb8,
+        )?,
+        labels: encode_webp(&labels_pixels, label_width, label_height, ColorType::Rgba8)?,
+    })
+}
+
+/// Check the length of an attribute with `stride` values per splat, and that every value is
+/// finite. A single NaN would otherwise spread through the bounds or codebook of the attribute.
+fn check_values(name: &str, values: &[f32], count: usize, stride: usize) -> EncodeResult<()> {
+    if values.len() != count * stride {
+        return Err(EncodeError::InvalidSize(format!(
+            "{} has {} elements, expected {}",
+            name,
+            values.len(),
+            count * stride
+        )));
+    }
+    if let Some(i) = values.iter().position(|v| !v.is_finite()) {
+        return Err(EncodeError::InvalidData(format!(
+            "{} of splat {} is not finite: {}",
+            name,
+            i / stride,
+            values[i]
+        )));
+    }
+    Ok(())
+}
+
+/// Quantize a [`Splat`] into SOG v2 images and codebooks.
+pub fn encode(splat: &Splat, options: EncodeOptions) -> Result<SogDataV2> {
+    let count = splat.count;
+    if count == 0 {
+        return Err(EncodeError::InvalidData("splat count is 0".to_string()).into());
+    }
+    let stored_count = u32::try_from(count)
+        .map_err(|_| EncodeError::InvalidSize(format!("too many splats: {}", count)))?;
+    check_values("position", &splat.position, count, 3)?;
+    check_values("rotation", &splat.rotation, count, 4)?;
+    check_values("scale", &splat.scale, count, 3)?;
+    check_values("sh_0", &splat.sh_0, count, 4)?;
+
+    let sh_n = match (&splat.sh_n, splat.sh_degree) {
+        (_, 0) => None,
+        (Some(sh_n), bands) => {
+            check_values("sh_n", sh_n, count, sh_coeff_count(bands)? * 3)?;
+            Some(encode_sh_n(sh_n, count, bands, &options)?)
+        }
+        (None, bands) => Err(EncodeError::InvalidData(format!(
+            "sh_degree is {} but sh_n is missing",
+            bands
+        )))?,
+    };
+
+    Ok(SogDataV2 {
+        count: stored_count,
+        antialias: splat.antialias,
+        means: encode_positions(&splat.position, count)?,
+        scales: encode_scales(&splat.scale, count, options.iterations)?,
+        quats: encode_rotations(&splat.rotation, count)?,
+        sh_0: encode_sh_0(&splat.sh_0, count, options.iterations)?,
+        sh_n,
+        names: None,
+    })
+}
@@ -1,54 +1,304 @@
-﻿use image_webp::DecodingError;
+﻿use crate::pack::EntryNames;
+use image_webp::{DecodingError, EncodingError};
+use std::fmt;
 use thiserror::Error;
 
+/// Error of any operation of the crate.
+///
+/// [`Error::code`] is a stable machine-readable identifier of the kind of error, and
+/// [`Error::entry`], [`Error::image`] and [`Error::splat_index`] tell where it happened
+/// when known. Underlying errors are available through [`std::error::Error::source`].
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
-    #[error("{0}")]
+    #[error(transparent)]
     Unzip(#[from] UnzipError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     SogParse(#[from] ParseError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     SogDecode(#[from] DecodeError),
+
+    #[error(transparent)]
+    SogEncode(#[from] EncodeError),
+
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// Stable identifier of the kind of error, e.g. `"image_not_found"`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Unzip(e) => e.code(),
+            Error::SogParse(e) => e.code(),
+            Error::SogDecode(e) => e.code(),
+            Error::SogEncode(e) => e.code(),
+            Error::Io(_) => "io",
+        }
+    }
+
+    /// Name of the archive entry involved, if any.
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            Error::SogParse(e) => e.entry(),
+            Error::SogDecode(e) => e.entry(),
+            _ => None,
+        }
+    }
+
+    /// Image involved, named after its `meta.json` field, e.g. `"means_l"` or `"shN_labels"`.
+    pub fn image(&self) -> Option<&'static str> {
+        match self {
+            Error::SogDecode(e) => e.image(),
+            _ => None,
+        }
+    }
+
+    /// Index of the splat involved, if any.
+    pub fn splat_index(&self) -> Option<usize> {
+        match self {
+            Error::SogDecode(e) => e.splat_index(),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum UnzipError {
-    #[error("Zip error: {0}")]
+    #[error("invalid zip archive")]
     Unzip(#[from] zip::result::ZipError),
 }
 
+impl UnzipError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            UnzipError::Unzip(_) => "invalid_zip",
+        }
+    }
+}
+
 pub type UnzipResult<T> = core::result::Result<T, UnzipError>;
 
 #[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
 pub enum ParseError {
     #[error("meta.json not found")]
     MetaJsonNotFound,
     #[error("meta.json is invalid data: {0}")]
     InvalidMetaJson(String),
-    #[error("Deserialize error: {0}")]
+    #[error("failed to deserialize meta.json")]
     DeserializeMetaJson(#[from] serde_json::Error),
-    #[error("invalid vector data")]
+    #[error("invalid vector data: {0}")]
     ParseVector(String),
-    #[error("invalid codebook length")]
+    #[error("invalid codebook: {0}")]
     ParseCodebook(String),
     #[error("image file not found: {0}")]
     ImageNotFound(String),
+    #[error("failed to read {0}")]
+    ReadFile(String, #[source] std::io::Error),
+    #[error(transparent)]
+    LimitExceeded(#[from] LimitExceeded),
+}
+
+impl ParseError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::MetaJsonNotFound => "meta_json_not_found",
+            ParseError::InvalidMetaJson(_) => "invalid_meta_json",
+            ParseError::DeserializeMetaJson(_) => "deserialize_meta_json",
+            ParseError::ParseVector(_) => "invalid_vector",
+            ParseError::ParseCodebook(_) => "invalid_codebook",
+            ParseError::ImageNotFound(_) => "image_not_found",
+            ParseError::ReadFile(..) => "read_file",
+            ParseError::LimitExceeded(_) => "limit_exceeded",
+        }
+    }
+
+    /// Name of the archive entry involved, if any.
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            ParseError::MetaJsonNotFound
+            | ParseError::InvalidMetaJson(_)
+            | ParseError::DeserializeMetaJson(_) => Some("meta.json"),
+            ParseError::ImageNotFound(name) | ParseError::ReadFile(name, _) => Some(name),
+            ParseError::LimitExceeded(e) => e.entry.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// A resource limit of [`Limits`](crate::Limits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Limit {
+    Entries,
+    DecompressedBytes,
+    Splats,
+    ImagePixels,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::Entries => "archive entries",
+            Limit::DecompressedBytes => "decompressed bytes",
+            Limit::Splats => "splat count",
+            Limit::ImagePixels => "image pixels",
+        })
+    }
+}
+
+/// Data exceeded a [`Limit`]. `actual` is a lower bound when the data was cut off
+/// as soon as it exceeded the limit.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{limit} limit of {max} exceeded: {actual}{}", entry.as_ref().map(|e| format!(" in {}", e)).unwrap_or_default())]
+pub struct LimitExceeded {
+    pub limit: Limit,
+    pub actual: u64,
+    pub max: u64,
+    /// Name of the archive entry involved, if any.
+    pub entry: Option<String>,
 }
 
 pub type ParseResult<T> = core::result::Result<T, ParseError>;
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum DecodeError {
-    #[error("Image not found: {0}")]
-    DecodeImage(#[from] DecodingError),
+    #[error("failed to decode {image} image")]
+    DecodeImage {
+        image: &'static str,
+        /// Name of the archive entry of the image, if known.
+        entry: Option<String>,
+        #[source]
+        source: DecodingError,
+    },
+    #[error("{image} image is {width}x{height}, expected at least {expected} pixels")]
+    ImageSize {
+        image: &'static str,
+        entry: Option<String>,
+        width: u32,
+        height: u32,
+        expected: usize,
+    },
+    #[error("invalid rotation mode {mode} at splat {splat_index}, expected 252 to 255")]
+    InvalidRotationMode {
+        splat_index: usize,
+        entry: Option<String>,
+        mode: u8,
+    },
+    #[error("shN label {label} at splat {splat_index} is out of range for {palette_count} entries")]
+    ShNLabelOutOfRange {
+        splat_index: usize,
+        entry: Option<String>,
+        label: usize,
+        palette_count: usize,
+    },
     #[error("Invalid size: {0}")]
     InvalidSize(String),
     #[error("Invalid data: {0}")]
     InvalidData(String),
 }
 
+impl DecodeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DecodeError::DecodeImage { .. } => "decode_image",
+            DecodeError::ImageSize { .. } => "image_size",
+            DecodeError::InvalidRotationMode { .. } => "invalid_rotation_mode",
+            DecodeError::ShNLabelOutOfRange { .. } => "shn_label_out_of_range",
+            DecodeError::InvalidSize(_) => "invalid_size",
+            DecodeError::InvalidData(_) => "invalid_data",
+        }
+    }
+
+    /// Image involved, named after its `meta.json` field, e.g. `"means_l"` or `"shN_labels"`.
+    pub fn image(&self) -> Option<&'static str> {
+        match self {
+            DecodeError::DecodeImage { image, .. } | DecodeError::ImageSize { image, .. } => {
+                Some(image)
+            }
+            DecodeError::InvalidRotationMode { .. } => Some("quats"),
+            DecodeError::ShNLabelOutOfRange { .. } => Some("shN_labels"),
+            _ => None,
+        }
+    }
+
+    /// Index of the splat involved, if any.
+    pub fn splat_index(&self) -> Option<usize> {
+        match self {
+            DecodeError::InvalidRotationMode { splat_index, .. }
+            | DecodeError::ShNLabelOutOfRange { splat_index, .. } => Some(*splat_index),
+            _ => None,
+        }
+    }
+
+    /// Name of the archive entry of the image involved, if known.
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            DecodeError::DecodeImage { entry, .. }
+            | DecodeError::ImageSize { entry, .. }
+            | DecodeError::InvalidRotationMode { entry, .. }
+            | DecodeError::ShNLabelOutOfRange { entry, .. } => entry.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Fill in the entry of the image involved from the entry names of the SOG data,
+    /// which the decoders, only knowing the image, leave empty.
+    pub(crate) fn in_entry(mut self, names: Option<&EntryNames>) -> Self {
+        let name = self
+            .image()
+            .zip(names)
+            .and_then(|(image, names)| names.image(image))
+            .map(str::to_string);
+        match &mut self {
+            DecodeError::DecodeImage { entry, .. }
+            | DecodeError::ImageSize { entry, .. }
+            | DecodeError::InvalidRotationMode { entry, .. }
+            | DecodeError::ShNLabelOutOfRange { entry, .. }
+                if entry.is_none() =>
+            {
+                *entry = name;
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
 pub type DecodeResult<T> = core::result::Result<T, DecodeError>;
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum EncodeError {
+    #[error("failed to encode image")]
+    EncodeImage(#[from] EncodingError),
+    #[error("failed to serialize meta.json")]
+    SerializeMetaJson(#[from] serde_json::Error),
+    #[error("failed to write zip archive")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Invalid size: {0}")]
+    InvalidSize(String),
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+}
+
+impl EncodeError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            EncodeError::EncodeImage(_) => "encode_image",
+            EncodeError::SerializeMetaJson(_) => "serialize_meta_json",
+            EncodeError::Zip(_) => "write_zip",
+            EncodeError::InvalidSize(_) => "invalid_size",
+            EncodeError::InvalidData(_) => "invalid_data",
+        }
+    }
+}
+
+pub type EncodeResult<T> = core::result::Result<T, EncodeError>;
@@ -0,0 +1,109 @@
+use crate::decode::{DecodeOptions, decode_with};
+use crate::error::Result;
+use crate::types::{SogData, Splat};
+use half::f16;
+
+/// Positions of a [`SplatF16`]. Half floats have about three significant digits,
+/// which is too coarse for positions of large scenes, so they can stay f32.
+#[derive(Debug, Clone)]
+pub enum Positions {
+    F32(Vec<f32>),
+    F16(Vec<f16>),
+}
+
+/// Half float counterpart of [`Splat`] with the same layout of every attribute.
+/// Rotation matrices and covariances are not included.
+#[derive(Debug, Clone)]
+pub struct SplatF16 {
+    pub count: usize,
+    pub antialias: bool,
+    pub sh_degree: usize,
+    pub position: Positions,
+    pub rotation: Vec<f16>,
+    pub scale: Vec<f16>,
+    pub sh_0: Vec<f16>,
+    pub sh_n: Option<Vec<f16>>,
+}
+
+fn to_f16(values: &[f32]) -> Vec<f16> {
+    values.iter().map(|v| f16::from_f32(*v)).collect()
+}
+
+impl Splat {
+    /// Convert the attributes to half floats. Positions are converted only if `f16_positions` is set.
+    pub fn to_f16(&self, f16_positions: bool) -> SplatF16 {
+        SplatF16 {
+            count: self.count,
+            antialias: self.antialias,
+            sh_degree: self.sh_degree,
+            position: if f16_positions {
+                Positions::F16(to_f16(&self.position))
+            } else {
+                Positions::F32(self.position.clone())
+            },
+            rotation: to_f16(&self.rotation),
+            scale: to_f16(&self.scale),
+            sh_0: to_f16(&self.sh_0),
+            sh_n: self.sh_n.as_deref().map(to_f16),
+        }
+    }
+}
+
+/// Decode SOG data into half floats. Positions are converted only if `f16_positions` is set.
+pub fn decode_f16(
+    sog_data: &SogData,
+    options: &DecodeOptions,
+    f16_positions: bool,
+) -> Result<SplatF16> {
+    // each attribute gets its own decoding pass and is converted before the next one,
+    // which keeps the f32 peak to the largest attribute
+    let only = |f: &dyn Fn(&mut DecodeOptions)| {
+        let mut options = DecodeOptions {
+            position: false,
+            rotation: false,
+            scale: false,
+            sh_0: false,
+            sh_n: false,
+            rotation_matrix: false,
+            covariance: false,
+            ..options.clone()
+        };
+        f(&mut options);
+        decode_with(sog_data, &options)
+    };
+
+    let position = if !options.position {
+        Positions::F32(Vec::new())
+    } else if f16_positions {
+        Positions::F16(to_f16(&only(&|o| o.position = true)?.position))
+    } else {
+        Positions::F32(only(&|o| o.position = true)?.position)
+    };
+    let rotation = if options.rotation {
+        to_f16(&only(&|o| o.rotation = true)?.rotation)
+    } else {
+        Vec::new()
+    };
+    let scale = if options.scale {
+        to_f16(&only(&|o| o.scale = true)?.scale)
+    } else {
+        Vec::new()
+    };
+    let sh_0 = if options.sh_0 {
+        to_f16(&only(&|o| o.sh_0 = true)?.sh_0)
+    } else {
+        Vec::new()
+    };
+    let sh_n = only(&|o| o.sh_n = options.sh_n)?;
+
+    Ok(SplatF16 {
+        count: sh_n.count,
+        antialias: sh_n.antialias,
+        sh_degree: sh_n.sh_degree,
+        position,
+        rotation,
+        scale,
+        sh_0,
+        sh_n: sh_n.sh_n.as_deref().map(to_f16),
+    })
+}
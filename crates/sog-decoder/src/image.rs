@@ -0,0 +1,89 @@
+use crate::error::{DecodeError, DecodeResult};
+use crate::inspect::ImageInfo;
+use image_webp::WebPDecoder;
+use std::io::Cursor;
+
+/// Decoded WebP image, always normalised to RGBA8.
+#[derive(Debug, Clone)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    pub fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Check that the image has at least `count` pixels.
+    pub(crate) fn check_pixel_count(&self, count: usize, image: &'static str) -> DecodeResult<()> {
+        if self.pixel_count() < count {
+            return Err(DecodeError::ImageSize {
+                image,
+                entry: None,
+                width: self.width,
+                height: self.height,
+                expected: count,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Dimensions and alpha from the headers of a WebP image, without decoding it.
+pub(crate) fn webp_header(data: &[u8]) -> Option<ImageInfo> {
+    let decoder = WebPDecoder::new(Cursor::new(data)).ok()?;
+    let (width, height) = decoder.dimensions();
+    Some(ImageInfo {
+        width,
+        height,
+        has_alpha: decoder.has_alpha(),
+    })
+}
+
+/// Dimensions and alpha from the fixed 30-byte header of an extended (`VP8X`) WebP image,
+/// which [`webp_header`] only reports after walking every chunk of the file.
+pub(crate) fn vp8x_header(data: &[u8]) -> Option<ImageInfo> {
+    if data.len() < 30 || &data[..4] != b"RIFF" || &data[8..16] != b"WEBPVP8X" {
+        return None;
+    }
+    let u24 = |b: &[u8]| u32::from_le_bytes([b[0], b[1], b[2], 0]);
+    Some(ImageInfo {
+        width: u24(&data[24..27]) + 1,
+        height: u24(&data[27..30]) + 1,
+        has_alpha: data[20] & 0x10 != 0,
+    })
+}
+
+/// Decode a WebP image. Images without alpha are expanded with an opaque alpha channel,
+/// so encoders which drop a fully opaque alpha channel decode the same.
+/// `image` names the image in errors.
+pub(crate) fn decode_webp(data: &[u8], image: &'static str) -> DecodeResult<RgbaImage> {
+    let error = |source| DecodeError::DecodeImage {
+        image,
+        entry: None,
+        source,
+    };
+    let cursor = Cursor::new(data);
+    let mut decoder = WebPDecoder::new(cursor).map_err(error)?;
+    let (width, height) = decoder.dimensions();
+    let output_size = decoder.output_buffer_size().ok_or_else(|| {
+        DecodeError::InvalidSize(format!("cannot determine output size of {image} image"))
+    })?;
+    let mut pixels = vec![0u8; output_size];
+    decoder.read_image(&mut pixels).map_err(error)?;
+
+    if !decoder.has_alpha() {
+        pixels = pixels
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect();
+    }
+
+    Ok(RgbaImage {
+        width,
+        height,
+        pixels,
+    })
+}
@@ -0,0 +1,166 @@
+use crate::decode::{palette_count_v1, read_meta_json, sh_coeff_count};
+use crate::error::{ParseError, Result};
+use crate::image::{vp8x_header, webp_header};
+use crate::kernels::unlog;
+use crate::legacy::sh_bands_v1;
+use crate::limits::Limits;
+use crate::metajson::{SogMeta, SogMetaV1, Version, detect_version};
+use crate::source::ZipSource;
+use crate::types::Vector3;
+use std::io::Cursor;
+
+/// Leading bytes of a compressed entry decompressed to find the WebP headers,
+/// which take 30 bytes in every format.
+const HEADER_PREFIX: u64 = 64;
+
+/// Scene properties of a `.sog` archive, see [`inspect`].
+#[derive(Debug, Clone)]
+pub struct SogInfo {
+    /// `meta.json` version, 1 for legacy SOGS.
+    pub version: u32,
+    pub count: usize,
+    /// SH bands of shN, 0 without shN.
+    pub sh_bands: usize,
+    /// Entries of the shN palette.
+    pub palette_count: Option<usize>,
+    pub antialias: bool,
+    /// Bounds of the positions, from `means.mins`/`means.maxs`.
+    pub bounds: Option<Bounds>,
+    /// Every entry of the archive, in directory order.
+    pub entries: Vec<EntryInfo>,
+}
+
+/// Axis-aligned bounding box, in the coordinate system of the file.
+#[derive(Debug, Clone, Default)]
+pub struct Bounds {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+#[derive(Debug, Clone)]
+pub struct EntryInfo {
+    pub name: String,
+    pub compressed_size: u64,
+    pub size: u64,
+    /// Headers of the entry if it is a WebP image.
+    pub image: Option<ImageInfo>,
+}
+
+/// Headers of a WebP image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub has_alpha: bool,
+}
+
+/// Read the scene properties of a bundled `.sog` archive without decoding it.
+/// Only the zip directory, `meta.json` and the headers of the WebP images are parsed;
+/// stored images are read in place and compressed ones decompressed as far as their headers.
+pub fn inspect(file: &[u8]) -> Result<SogInfo> {
+    inspect_with_limits(file, &Limits::NONE)
+}
+
+/// Same as [`inspect`], enforcing the limits on archive entries and decompressed bytes.
+/// Nothing is allocated per splat or pixel, so the other limits do not apply.
+pub fn inspect_with_limits(file: &[u8], limits: &Limits) -> Result<SogInfo> {
+    let source = ZipSource::new_with_limits(Cursor::new(file), *limits)?;
+    let meta_json = read_meta_json(&source)?;
+
+    let (mut info, centroids) = match detect_version(&meta_json)? {
+        Version::V1 => {
+            let meta_json = serde_json::from_value::<SogMetaV1>(meta_json)
+                .map_err(ParseError::DeserializeMetaJson)?;
+            let centroids = meta_json
+                .sh_n
+                .as_ref()
+                .and_then(|s| s.files.first().cloned());
+            (inspect_v1(&meta_json), centroids)
+        }
+        Version::V2 => {
+            let meta_json = serde_json::from_value::<SogMeta>(meta_json)
+                .map_err(ParseError::DeserializeMetaJson)?;
+            (inspect_v2(&meta_json), None)
+        }
+    };
+
+    info.entries = source
+        .entries()?
+        .into_iter()
+        .map(|(name, compressed_size, size)| EntryInfo {
+            image: image_header(&source, file, &name),
+            name,
+            compressed_size,
+            size,
+        })
+        .collect();
+
+    if let Some(centroids) = centroids
+        && info.sh_bands > 0
+    {
+        let coeff_count = sh_coeff_count(info.sh_bands);
+        info.palette_count = info
+            .entries
+            .iter()
+            .find(|entry| entry.name == centroids)
+            .and_then(|entry| entry.image)
+            .map(|image| {
+                palette_count_v1(image.width as usize * image.height as usize, coeff_count)
+            });
+    }
+    Ok(info)
+}
+
+fn inspect_v2(meta_json: &SogMeta) -> SogInfo {
+    let sh_n = meta_json.sh_n.as_ref();
+    SogInfo {
+        version: 2,
+        count: meta_json.count as usize,
+        sh_bands: sh_n.map_or(0, |s| s.bands.max(0) as usize),
+        palette_count: sh_n.map(|s| s.count.max(0) as usize),
+        antialias: meta_json.antialias.unwrap_or(false),
+        bounds: bounds(&meta_json.means.mins, &meta_json.means.maxs),
+        entries: Vec::new(),
+    }
+}
+
+fn inspect_v1(meta_json: &SogMetaV1) -> SogInfo {
+    let sh_bands = meta_json
+        .sh_n
+        .as_ref()
+        .and_then(|s| sh_bands_v1(&s.shape))
+        .map_or(0, |bands| bands as usize);
+    let means = &meta_json.means;
+    let mins = means.mins.as_ref().and_then(|b| b.to_vec(3));
+    let maxs = means.maxs.as_ref().and_then(|b| b.to_vec(3));
+    SogInfo {
+        version: 1,
+        count: means.shape.first().copied().unwrap_or(0),
+        sh_bands,
+        palette_count: None,
+        antialias: false,
+        bounds: mins.zip(maxs).and_then(|(mins, maxs)| bounds(&mins, &maxs)),
+        entries: Vec::new(),
+    }
+}
+
+/// Positions are stored with a log transform, which is monotonic, so the bounds are the
+/// transformed `mins` and `maxs`.
+fn bounds(mins: &[f32], maxs: &[f32]) -> Option<Bounds> {
+    let vector = |v: &[f32]| match v {
+        [x, y, z, ..] => Some(Vector3::new(unlog(*x), unlog(*y), unlog(*z))),
+        _ => None,
+    };
+    Some(Bounds {
+        min: vector(mins)?,
+        max: vector(maxs)?,
+    })
+}
+
+fn image_header(source: &ZipSource<Cursor<&[u8]>>, file: &[u8], name: &str) -> Option<ImageInfo> {
+    if let Some(data) = source.stored_range(name).and_then(|range| file.get(range)) {
+        return webp_header(data);
+    }
+    let prefix = source.read_prefix(name, HEADER_PREFIX).ok()?;
+    vp8x_header(&prefix).or_else(|| webp_header(&prefix))
+}
@@ -0,0 +1,300 @@
+//! Inner loops of the v2 decoders. With the `simd` feature they are vectorised, on SSE, NEON
+//! or wasm `simd128` where the target enables it, and the scalar ones are the reference they
+//! are tested against. Codebook lookups are gathers with no portable vector form, so the
+//! vector sh0 and shN kernels move the lookups out of the per-splat work instead.
+
+#[cfg(not(feature = "simd"))]
+pub(crate) use scalar::{Sh0Kernel, ShNKernel, positions};
+#[cfg(feature = "simd")]
+pub(crate) use simd::{Sh0Kernel, ShNKernel, positions};
+
+pub(crate) fn unlog(x: f32) -> f32 {
+    f32::signum(x) * (f32::exp(f32::abs(x)) - 1.0)
+}
+
+/// https://github.com/playcanvas/splat-transform/blob/930a9aec511af3665240589b9cf1727d5dcd2eac/src/lib/readers/read-sog.ts#L174
+fn sigmoid_inv(y: f32) -> f32 {
+    let e = y.clamp(1e-6, 1.0 - 1e-6);
+    (e / (1.0 - e)).ln()
+}
+
+mod scalar {
+    use super::sigmoid_inv;
+    use crate::types::Codebook;
+
+    #[cfg(any(not(feature = "simd"), test))]
+    fn lerp(a: f32, b: f32, t: f32) -> f32 {
+        a + t * (b - a)
+    }
+
+    /// Join the 16-bit quantized positions from the RGBA pixels of `means_l` and `means_u`
+    /// and undo the log transform, for as many splats as `out` holds.
+    #[cfg(any(not(feature = "simd"), test))]
+    pub fn positions(lower: &[u8], upper: &[u8], mins: [f32; 3], maxs: [f32; 3], out: &mut [f32]) {
+        for (i, position) in out.chunks_exact_mut(3).enumerate() {
+            for c in 0..3 {
+                let pos = ((upper[i * 4 + c] as u16) << 8) | (lower[i * 4 + c] as u16);
+                position[c] = super::unlog(lerp(mins[c], maxs[c], pos as f32 / 65535.0));
+            }
+        }
+    }
+
+    pub struct Sh0Kernel<'a> {
+        codebook: &'a Codebook,
+        opacities: [f32; 256],
+    }
+
+    impl<'a> Sh0Kernel<'a> {
+        pub fn new(codebook: &'a Codebook) -> Self {
+            Self {
+                codebook,
+                opacities: std::array::from_fn(|a| sigmoid_inv(a as f32 / 255.0)),
+            }
+        }
+
+        /// Color from the codebook and opacity as a logit from an RGBA pixel of `sh0`.
+        pub fn splat(&self, pixel: &[u8], out: &mut [f32]) {
+            out[0] = self.codebook.0[pixel[0] as usize];
+            out[1] = self.codebook.0[pixel[1] as usize];
+            out[2] = self.codebook.0[pixel[2] as usize];
+            out[3] = self.opacities[pixel[3] as usize];
+        }
+    }
+
+    pub struct ShNKernel<'a> {
+        codebook: &'a Codebook,
+        centroids: &'a [u8],
+        coeff_count: usize,
+        out_coeff_count: usize,
+    }
+
+    impl<'a> ShNKernel<'a> {
+        /// `centroids` holds `coeff_count` RGBA pixels per palette entry,
+        /// of which the first `out_coeff_count` are output.
+        pub fn new(
+            codebook: &'a Codebook,
+            centroids: &'a [u8],
+            coeff_count: usize,
+            out_coeff_count: usize,
+        ) -> Self {
+            Self {
+                codebook,
+                centroids,
+                coeff_count,
+                out_coeff_count,
+            }
+        }
+
+        /// Coefficients of a palette entry, laid out as for one splat of `Splat::sh_n`.
+        /// The caller checks that the entry is in the palette.
+        pub fn splat(&self, palette_index: usize, out: &mut [f32]) {
+            for i in 0..3 {
+                for coeff_index in 0..self.out_coeff_count {
+                    let index = i * self.out_coeff_count + coeff_index;
+                    let index2 = (palette_index * self.coeff_count + coeff_index) * 4 + i;
+                    out[index] = self.codebook.0[self.centroids[index2] as usize];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "simd")]
+mod simd {
+    use super::scalar;
+    use crate::types::Codebook;
+    use wide::f32x4;
+
+    /// Same as [`super::scalar::positions`] four splats at a time, as three vectors of their
+    /// interleaved components. The lerp is reassociated and `exp` is approximated, so results
+    /// differ from the scalar kernel by about 1e-6 relative.
+    pub fn positions(lower: &[u8], upper: &[u8], mins: [f32; 3], maxs: [f32; 3], out: &mut [f32]) {
+        let lanes =
+            |v: [f32; 3]| [0, 1, 2].map(|k| f32x4::from([0, 1, 2, 3].map(|l| v[(k * 4 + l) % 3])));
+        let steps = lanes([0, 1, 2].map(|c| (maxs[c] - mins[c]) / 65535.0));
+        let mins = lanes(mins);
+
+        // four RGBA pixels of each image to twelve positions
+        let block = |lower: &[u8; 16], upper: &[u8; 16]| {
+            let joined: [f32; 12] = std::array::from_fn(|j| {
+                let p = j / 3 * 4 + j % 3;
+                // u16 fits in f32 exactly
+                (((upper[p] as u16) << 8) | (lower[p] as u16)) as f32
+            });
+            let mut result = [0f32; 12];
+            for k in 0..3 {
+                let v = mins[k] + f32x4::from([0, 1, 2, 3].map(|l| joined[k * 4 + l])) * steps[k];
+                // the approximated exp does not overflow to infinity, its square does
+                let half = (v.abs().min(f32x4::splat(100.0)) * 0.5).exp();
+                let position = (half * half - 1.0).copysign(v);
+                result[k * 4..k * 4 + 4].copy_from_slice(position.as_array_ref());
+            }
+            result
+        };
+
+        let start = out.len() / 12 * 16;
+        let mut blocks = out.chunks_exact_mut(12);
+        let mut pixels = lower.chunks_exact(16).zip(upper.chunks_exact(16));
+        for (out, (lower, upper)) in (&mut blocks).zip(&mut pixels) {
+            out.copy_from_slice(&block(lower.try_into().unwrap(), upper.try_into().unwrap()));
+        }
+
+        // the remaining splats are padded to a full block
+        let out = blocks.into_remainder();
+        if !out.is_empty() {
+            let splats = out.len() / 3;
+            let (mut lower_tail, mut upper_tail) = ([0u8; 16], [0u8; 16]);
+            lower_tail[..splats * 4].copy_from_slice(&lower[start..start + splats * 4]);
+            upper_tail[..splats * 4].copy_from_slice(&upper[start..start + splats * 4]);
+            out.copy_from_slice(&block(&lower_tail, &upper_tail)[..out.len()]);
+        }
+    }
+
+    /// Same as [`scalar::Sh0Kernel`] with the codebook and opacity tables merged, so that a
+    /// splat is one vector built from four loads of the same table. The loads bound it, and
+    /// it runs about as fast as the scalar kernel.
+    pub struct Sh0Kernel {
+        /// Codebook value in the color lanes and opacity in the last one, per byte.
+        table: [[f32; 4]; 256],
+    }
+
+    impl Sh0Kernel {
+        pub fn new(codebook: &Codebook) -> Self {
+            let scalar = scalar::Sh0Kernel::new(codebook);
+            Self {
+                table: std::array::from_fn(|byte| {
+                    let mut values = [0.0; 4];
+                    scalar.splat(&[byte as u8; 4], &mut values);
+                    values
+                }),
+            }
+        }
+
+        pub fn splat(&self, pixel: &[u8], out: &mut [f32]) {
+            let value = f32x4::from([0, 1, 2, 3].map(|c| self.table[pixel[c] as usize][c]));
+            out[..4].copy_from_slice(value.as_array_ref());
+        }
+    }
+
+    /// Same as [`scalar::ShNKernel`] with every palette entry dequantised up front, so that
+    /// a splat is a copy of its entry instead of a codebook lookup per coefficient.
+    /// Encoders keep the palette below the splat count, so this does fewer lookups.
+    pub struct ShNKernel {
+        entries: Vec<f32>,
+        entry_len: usize,
+    }
+
+    impl ShNKernel {
+        pub fn new(
+            codebook: &Codebook,
+            centroids: &[u8],
+            coeff_count: usize,
+            out_coeff_count: usize,
+        ) -> Self {
+            let scalar = scalar::ShNKernel::new(codebook, centroids, coeff_count, out_coeff_count);
+            let entry_len = out_coeff_count * 3;
+            let palette_count = centroids.len() / (coeff_count * 4);
+            let mut entries = vec![0.0; palette_count * entry_len];
+            for (palette_index, entry) in entries.chunks_exact_mut(entry_len).enumerate() {
+                scalar.splat(palette_index, entry);
+            }
+            Self { entries, entry_len }
+        }
+
+        pub fn splat(&self, palette_index: usize, out: &mut [f32]) {
+            let start = palette_index * self.entry_len;
+            out.copy_from_slice(&self.entries[start..start + self.entry_len]);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
+mod tests {
+    use super::{scalar, simd};
+    use crate::types::Codebook;
+
+    /// Relative tolerance of the approximated `exp` in the position kernel.
+    const TOLERANCE: f32 = 1e-5;
+
+    #[test]
+    fn simd_positions_match_scalar() {
+        // every 16-bit value in each channel, and splat counts that leave a partial block
+        let values = (0..=u16::MAX).collect::<Vec<_>>();
+        let pixels = |shift: u16| {
+            values
+                .chunks(3)
+                .flat_map(|v| [0, 1, 2, 3].map(|c| v.get(c).map_or(0, |v| (v >> shift) as u8)))
+                .collect::<Vec<_>>()
+        };
+        let (lower, upper) = (pixels(0), pixels(8));
+        // positions are stored as log(|x| + 1); real scenes stay well within ±20
+        let ranges = [
+            ([-1.0, -2.0, -3.0], [1.0, 2.0, 3.0]),
+            ([0.0, 0.0, 0.0], [0.0, 0.0, 0.0]),
+            ([-19.5, 0.25, -7.0], [19.5, 12.0, -0.5]),
+            ([5.0, 3.0, 1.0], [-5.0, -3.0, -1.0]),
+            // beyond the range of f32
+            ([-100.0, 80.0, -90.0], [100.0, 95.0, 90.0]),
+        ];
+        for (mins, maxs) in ranges {
+            // the reassociated lerp rounds in steps of the bounds
+            let scale = mins
+                .iter()
+                .chain(&maxs)
+                .fold(20f32, |m, v: &f32| m.max(v.abs()))
+                / 20.0;
+            for splats in [lower.len() / 4, lower.len() / 4 - 1, 3, 1, 0] {
+                let (mut a, mut b) = (vec![0f32; splats * 3], vec![0f32; splats * 3]);
+                scalar::positions(&lower, &upper, mins, maxs, &mut a);
+                simd::positions(&lower, &upper, mins, maxs, &mut b);
+                for (i, (a, b)) in a.iter().zip(&b).enumerate() {
+                    if a.is_infinite() {
+                        assert_eq!(a, b, "position {i}");
+                        continue;
+                    }
+                    let tolerance = TOLERANCE * scale * a.abs().max(b.abs()).max(1.0);
+                    assert!((a - b).abs() <= tolerance, "position {i}: {a} != {b}");
+                }
+            }
+        }
+    }
+
+    fn codebook() -> Codebook {
+        Codebook(std::array::from_fn(|i| (i as f32 - 127.5) / 64.0))
+    }
+
+    #[test]
+    fn simd_sh_0_matches_scalar() {
+        let codebook = codebook();
+        let (a, b) = (
+            scalar::Sh0Kernel::new(&codebook),
+            simd::Sh0Kernel::new(&codebook),
+        );
+        for pixel in (0..=255u8).map(|v| [v, 255 - v, v / 2, v.wrapping_mul(7)]) {
+            let (mut expected, mut actual) = ([0.0; 4], [0.0; 4]);
+            a.splat(&pixel, &mut expected);
+            b.splat(&pixel, &mut actual);
+            assert_eq!(expected, actual, "pixel {pixel:?}");
+        }
+    }
+
+    #[test]
+    fn simd_sh_n_matches_scalar() {
+        let codebook = codebook();
+        let (coeff_count, palette_count) = (15, 300);
+        let centroids = (0..palette_count * coeff_count * 4)
+            .map(|i| (i * 31 % 256) as u8)
+            .collect::<Vec<_>>();
+        for out_coeff_count in [3, 8, 15] {
+            let a = scalar::ShNKernel::new(&codebook, &centroids, coeff_count, out_coeff_count);
+            let b = simd::ShNKernel::new(&codebook, &centroids, coeff_count, out_coeff_count);
+            for palette_index in 0..palette_count {
+                let mut expected = vec![0.0; out_coeff_count * 3];
+                let mut actual = vec![0.0; out_coeff_count * 3];
+                a.splat(palette_index, &mut expected);
+                b.splat(palette_index, &mut actual);
+                assert_eq!(expected, actual, "palette entry {palette_index}");
+            }
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use crate::coordinate::Transform;
+use crate::decode::{
+    DecodeOptions, decode_positions, decode_rotations, decode_scales, decode_sh_0, decode_sh_n,
+    sh_coeff_count,
+};
+use crate::error::{DecodeError, DecodeResult, Result};
+use crate::rotation::{covariances, normalize_quaternions, reorder_quaternions, rotation_matrices};
+use crate::types::{SogDataV2, activate_colors, activate_scales};
+use std::borrow::Cow;
+
+/// A splat attribute stored in an interleaved record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Attribute {
+    /// x, y, z
+    Position,
+    /// Quaternion in the order of `DecodeOptions::quaternion_order`.
+    Rotation,
+    /// Row-major 3x3 rotation matrix.
+    RotationMatrix,
+    /// 3D covariance `xx, xy, xz, yy, yz, zz`.
+    Covariance,
+    /// x, y, z
+    Scale,
+    /// r, g, b, opacity
+    Sh0,
+    /// shN coefficients up to `bands` bands, in the same order as
+    /// [`Splat::sh_n`](crate::types::Splat::sh_n). Higher bands of the data are dropped
+    /// and missing bands are filled with zeros, so the record size does not depend on the data.
+    ShN { bands: usize },
+}
+
+impl Attribute {
+    /// Number of components of the attribute.
+    pub fn components(&self) -> usize {
+        match self {
+            Attribute::Position | Attribute::Scale => 3,
+            Attribute::Rotation | Attribute::Sh0 => 4,
+            Attribute::RotationMatrix => 9,
+            Attribute::Covariance => 6,
+            Attribute::ShN { bands } => sh_coeff_count(*bands).saturating_mul(3),
+        }
+    }
+}
+
+/// Type of every component of an attribute, written in little endian.
+/// `F16` is only available with the `f16` feature, so matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ComponentType {
+    F32,
+    #[cfg(feature = "f16")]
+    F16,
+}
+
+impl ComponentType {
+    /// Size of a component in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            ComponentType::F32 => 4,
+            #[cfg(feature = "f16")]
+            ComponentType::F16 => 2,
+        }
+    }
+
+    fn write(&self, value: f32, out: &mut [u8]) {
+        match self {
+            ComponentType::F32 => out[..4].copy_from_slice(&value.to_le_bytes()),
+            #[cfg(feature = "f16")]
+            ComponentType::F16 => {
+                out[..2].copy_from_slice(&half::f16::from_f32(value).to_le_bytes())
+            }
+        }
+    }
+}
+
+/// Placement of an attribute in a record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayoutElement {
+    pub attribute: Attribute,
+    pub ty: ComponentType,
+    /// Byte offset from the start of the record.
+    pub offset: usize,
+}
+
+impl LayoutElement {
+    /// Size of the element in bytes.
+    pub fn size(&self) -> usize {
+        self.attribute.components().saturating_mul(self.ty.size())
+    }
+}
+
+/// Layout of an interleaved buffer with one record of `stride` bytes per splat.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterleavedLayout {
+    pub elements: Vec<LayoutElement>,
+    pub stride: usize,
+}
+
+impl InterleavedLayout {
+    /// Attributes placed back to back in the given order without padding.
+    /// Set `stride` afterwards to pad the records, e.g. for alignment.
+    pub fn packed(attributes: &[(Attribute, ComponentType)]) -> Self {
+        let mut offset = 0;
+        let elements = attributes
+            .iter()
+            .map(|(attribute, ty)| {
+                let element = LayoutElement {
+                    attribute: *attribute,
+                    ty: *ty,
+                    offset,
+                };
+                offset = offset.saturating_add(element.size());
+                element
+            })
+            .collect();
+        Self {
+            elements,
+            stride: offset,
+        }
+    }
+
+    /// Size in bytes of a buffer holding `count` records.
+    pub fn buffer_size(&self, count: usize) -> Option<usize> {
+        count.checked_mul(self.stride)
+    }
+
+    fn validate(&self) -> DecodeResult<()> {
+        if self.stride == 0 {
+            return Err(DecodeError::InvalidSize("stride is 0".to_string()));
+        }
+        for (i, element) in self.elements.iter().enumerate() {
+            if let Attribute::ShN { bands } = element.attribute
+                && !(1..=3).contains(&bands)
+            {
+                return Err(DecodeError::InvalidData(format!(
+                    "shN bands is {bands}, expected 1 to 3"
+                )));
+            }
+            let fits = element
+                .offset
+                .checked_add(element.size())
+                .is_some_and(|end| end <= self.stride);
+            if !fits {
+                return Err(DecodeError::InvalidSize(format!(
+                    "{:?} at offset {} does not fit in stride {}",
+                    element.attribute, element.offset, self.stride
+                )));
+            }
+            for other in &self.elements[..i] {
+                if std::mem::discriminant(&element.attribute)
+                    == std::mem::discriminant(&other.attribute)
+                {
+                    return Err(DecodeError::InvalidData(format!(
+                        "duplicated attribute: {:?}",
+                        element.attribute
+                    )));
+                }
+                let overlaps = element.offset < other.offset + other.size()
+                    && other.offset < element.offset + element.size();
+                if overlaps {
+                    return Err(DecodeError::InvalidData(format!(
+                        "{:?} overlaps {:?}",
+                        element.attribute, other.attribute
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Values of an attribute used by several elements, decoded into `cache` on first use.
+/// At the `last` use they are moved out instead of borrowed.
+fn shared(
+    cache: &mut Option<Vec<f32>>,
+    last: bool,
+    decode: impl FnOnce() -> DecodeResult<Vec<f32>>,
+) -> DecodeResult<Cow<'_, [f32]>> {
+    if cache.is_none() {
+        *cache = Some(decode()?);
+    }
+    if last {
+        return Ok(Cow::Owned(cache.take().unwrap_or_default()));
+    }
+    Ok(Cow::Borrowed(cache.as_deref().unwrap_or_default()))
+}
+
+/// Decode SOG v2 data into a new interleaved buffer.
+/// The attributes come from the layout; `max_sh_degree`, `activate`, `quaternion_order`,
+/// `normalize_rotations` and `coordinate_system` of the options apply.
+pub fn decode_interleaved<I: AsRef<[u8]>>(
+    sog_data: &SogDataV2<I>,
+    layout: &InterleavedLayout,
+    options: &DecodeOptions,
+) -> Result<Vec<u8>> {
+    layout.validate()?;
+    let size = layout
+        .buffer_size(sog_data.count as usize)
+        .ok_or_else(|| DecodeError::InvalidSize(format!("too many splats: {}", sog_data.count)))?;
+    let mut out = vec![0u8; size];
+    decode_interleaved_into(sog_data, layout, options, &mut out)?;
+    Ok(out)
+}
+
+/// Same as [`decode_interleaved`], writing into a caller-provided buffer.
+/// Bytes not covered by the layout are left untouched.
+pub fn decode_interleaved_into<I: AsRef<[u8]>>(
+    sog_data: &SogDataV2<I>,
+    layout: &InterleavedLayout,
+    options: &DecodeOptions,
+    out: &mut [u8],
+) -> Result<()> {
+    layout.validate()?;
+
+    let count = sog_data.count as usize;
+    let size = layout
+        .buffer_size(count)
+        .ok_or_else(|| DecodeError::InvalidSize(format!("too many splats: {}", count)))?;
+    if out.len() < size {
+        return Err(DecodeError::InvalidSize(format!(
+            "interleaved buffer has {} bytes, expected at least {}",
+            out.len(),
+            size
+        ))
+        .into());
+    }
+
+    let transform = Transform::new(&options.coordinate_system)?;
+    let in_entry = |e: DecodeError| e.in_entry(sog_data.names.as_ref());
+    let rotations = || -> DecodeResult<Vec<f32>> {
+        let mut rotations =
+            decode_rotations(&sog_data.quats, count, false, Vec::new()).map_err(in_entry)?;
+        if let Some(transform) = &transform {
+            transform.rotations(&mut rotations);
+        }
+        Ok(rotations)
+    };
+    let scales = || -> DecodeResult<Vec<f32>> {
+        let mut scales = decode_scales(&sog_data.scales, count, Vec::new()).map_err(in_entry)?;
+        if let Some(transform) = &transform {
+            transform.scales(&mut scales);
+        }
+        Ok(scales)
+    };
+
+    // rotations and scales feed several attributes, so they are decoded once and kept
+    // until the last element using them; other attributes are dropped after their element
+    let last_use = |uses: fn(Attribute) -> bool| {
+        layout
+            .elements
+            .iter()
+            .rposition(|element| uses(element.attribute))
+    };
+    let last_rotation_use = last_use(|attribute| {
+        matches!(
+            attribute,
+            Attribute::Rotation | Attribute::RotationMatrix | Attribute::Covariance
+        )
+    });
+    let last_scale_use =
+        last_use(|attribute| matches!(attribute, Attribute::Scale | Attribute::Covariance));
+    let (mut rotation_cache, mut scale_cache) = (None, None);
+
+    for (index, element) in layout.elements.iter().enumerate() {
+        let last_rotation = last_rotation_use == Some(index);
+        let last_scale = last_scale_use == Some(index);
+        let components = element.attribute.components();
+        let values = match element.attribute {
+            Attribute::Position => {
+                let mut positions =
+                    decode_positions(&sog_data.means, count, Vec::new()).map_err(in_entry)?;
+                if let Some(transform) = &transform {
+                    transform.positions(&mut positions);
+                }
+                positions
+            }
+            Attribute::Rotation => {
+                let mut rotations =
+                    shared(&mut rotation_cache, last_rotation, rotations)?.into_owned();
+                if options.normalize_rotations {
+                    normalize_quaternions(&mut rotations);
+                }
+                reorder_quaternions(&mut rotations, options.quaternion_order);
+                rotations
+            }
+            Attribute::RotationMatrix => {
+                rotation_matrices(&shared(&mut rotation_cache, last_rotation, rotations)?)
+            }
+            Attribute::Covariance => covariances(
+                &shared(&mut rotation_cache, last_rotation, rotations)?,
+                &shared(&mut scale_cache, last_scale, scales)?,
+            ),
+            Attribute::Scale => {
+                let mut scales = shared(&mut scale_cache, last_scale, scales)?.into_owned();
+                if options.activate {
+                    activate_scales(&mut scales);
+                }
+                scales
+            }
+            Attribute::Sh0 => {
+                let mut colors =
+                    decode_sh_0(&sog_data.sh_0, count, Vec::new()).map_err(in_entry)?;
+                if options.activate {
+                    activate_colors(&mut colors);
+                }
+                colors
+            }
+            Attribute::ShN { bands } => {
+                let max_bands = bands.min(options.max_sh_degree.unwrap_or(usize::MAX));
+                match &sog_data.sh_n {
+                    Some(sh_n) if max_bands > 0 => {
+                        let mut coeffs =
+                            decode_sh_n(sh_n, count, max_bands, Vec::new()).map_err(in_entry)?;
+                        if let Some(transform) = &transform {
+                            let coeff_count = sh_coeff_count(max_bands.min(sh_n.bands as usize));
+                            transform.sh_n(&mut coeffs, coeff_count);
+                        }
+                        coeffs
+                    }
+                    _ => Vec::new(),
+                }
+            }
+        };
+
+        // shN may hold fewer coefficients than the layout reserves
+        let (values_coeff_count, coeff_count) = match element.attribute {
+            Attribute::ShN { bands } => (
+                values.len().checked_div(count * 3).unwrap_or(0),
+                sh_coeff_count(bands),
+            ),
+            _ => (components, components),
+        };
+        let component_size = element.ty.size();
+
+        for (i, record) in out[..size].chunks_exact_mut(layout.stride).enumerate() {
+            let record = &mut record[element.offset..element.offset + element.size()];
+            record.fill(0);
+            if values_coeff_count == 0 {
+                continue;
+            }
+            let channels = components / coeff_count;
+            for channel in 0..channels {
+                for coeff in 0..values_coeff_count {
+                    let value = values[(i * channels + channel) * values_coeff_count + coeff];
+                    let component = channel * coeff_count + coeff;
+                    element
+                        .ty
+                        .write(value, &mut record[component * component_size..]);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejects(layout: InterleavedLayout) {
+        assert!(layout.validate().is_err(), "{layout:?}");
+    }
+
+    #[test]
+    fn packed_layout_is_valid() {
+        let layout = InterleavedLayout::packed(&[
+            (Attribute::Position, ComponentType::F32),
+            (Attribute::ShN { bands: 3 }, ComponentType::F32),
+        ]);
+        assert_eq!(layout.stride, 12 + 180);
+        layout.validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_zero_stride() {
+        rejects(InterleavedLayout::packed(&[]));
+    }
+
+    #[test]
+    fn rejects_sh_n_without_bands() {
+        rejects(InterleavedLayout::packed(&[(
+            Attribute::ShN { bands: 0 },
+            ComponentType::F32,
+        )]));
+    }
+
+    #[test]
+    fn rejects_sh_n_above_3_bands() {
+        rejects(InterleavedLayout::packed(&[(
+            Attribute::ShN { bands: 4 },
+            ComponentType::F32,
+        )]));
+    }
+
+    #[test]
+    fn rejects_element_past_stride() {
+        let mut layout = InterleavedLayout::packed(&[(Attribute::Position, ComponentType::F32)]);
+        layout.stride = 8;
+        rejects(layout);
+    }
+
+    #[test]
+    fn rejects_overflowing_offset() {
+        let mut layout = InterleavedLayout::packed(&[(Attribute::Position, ComponentType::F32)]);
+        layout.elements[0].offset = usize::MAX - 4;
+        rejects(layout);
+    }
+
+    #[test]
+    fn rejects_huge_sh_n_bands() {
+        rejects(InterleavedLayout::packed(&[
+            (Attribute::Position, ComponentType::F32),
+            (Attribute::ShN { bands: usize::MAX }, ComponentType::F32),
+        ]));
+    }
+
+    #[test]
+    fn rejects_overlapping_elements() {
+        let mut layout = InterleavedLayout::packed(&[
+            (Attribute::Position, ComponentType::F32),
+            (Attribute::Scale, ComponentType::F32),
+        ]);
+        layout.elements[1].offset = 8;
+        rejects(layout);
+    }
+}
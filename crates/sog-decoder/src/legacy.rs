@@ -0,0 +1,269 @@
+use crate::decode::{
+    AttributeDecoders, DecodeOptions, decode_positions, decode_rotations, decode_splat, in_field,
+    palette_count_v1, read_image, sh_coeff_count, sh_n_coeff_count,
+};
+use crate::error::{DecodeError, DecodeResult, ParseError, ParseResult, Result};
+use crate::image::decode_webp;
+use crate::metajson::{AttributeMetaV1, SogMetaV1};
+use crate::pack::EntryNames;
+use crate::parallel::{for_each_splat, join};
+use crate::source::SogSource;
+use crate::types::{Means, Quats, ScalesV1, Sh0V1, ShNV1, SogDataV1, Splat, Vector3};
+use crate::validate::Finding;
+
+fn file_name<'a>(attribute: &'a AttributeMetaV1, index: usize, name: &str) -> ParseResult<&'a str> {
+    attribute
+        .files
+        .get(index)
+        .map(String::as_str)
+        .ok_or_else(|| ParseError::InvalidMetaJson(format!("missing {} file name", name)))
+}
+
+fn bounds(
+    attribute: &AttributeMetaV1,
+    len: usize,
+    name: &str,
+) -> ParseResult<(Vec<f32>, Vec<f32>)> {
+    let mins = attribute.mins.as_ref().and_then(|b| b.to_vec(len));
+    let maxs = attribute.maxs.as_ref().and_then(|b| b.to_vec(len));
+    match (mins, maxs) {
+        (Some(mins), Some(maxs)) => Ok((mins, maxs)),
+        _ => Err(ParseError::InvalidMetaJson(format!(
+            "missing or short {} mins/maxs",
+            name
+        ))),
+    }
+}
+
+/// SH bands of a v1 shN `shape`, which is the shape of the decoded coefficients,
+/// [splats, coefficients(, 3)]; either coefficient or float counts are accepted.
+pub(crate) fn sh_bands_v1(shape: &[usize]) -> Option<i32> {
+    let size = shape
+        .iter()
+        .skip(1)
+        .try_fold(1usize, |acc, v| acc.checked_mul(*v));
+    match size {
+        Some(3 | 9) => Some(1),
+        Some(8 | 24) => Some(2),
+        Some(15 | 45) => Some(3),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_sog_v1<S: SogSource + ?Sized>(
+    source: &S,
+    meta_json: SogMetaV1,
+) -> ParseResult<SogDataV1> {
+    let count = *meta_json
+        .means
+        .shape
+        .first()
+        .ok_or(ParseError::InvalidMetaJson(
+            "missing means shape".to_string(),
+        ))?;
+    let count = u32::try_from(count)
+        .map_err(|_| ParseError::InvalidMetaJson(format!("too many splats: {}", count)))?;
+
+    let mut names = EntryNames {
+        means_l: file_name(&meta_json.means, 0, "means_l")?.to_string(),
+        means_u: file_name(&meta_json.means, 1, "means_u")?.to_string(),
+        scales: file_name(&meta_json.scales, 0, "scales")?.to_string(),
+        quats: file_name(&meta_json.quats, 0, "quats")?.to_string(),
+        sh_0: file_name(&meta_json.sh0, 0, "sh0")?.to_string(),
+        ..EntryNames::default()
+    };
+
+    let (mins, maxs) = bounds(&meta_json.means, 3, "means")?;
+    let means = Means {
+        mins: mins.try_into().map_err(in_field("means.mins"))?,
+        maxs: maxs.try_into().map_err(in_field("means.maxs"))?,
+        means_l: read_image(source, &names.means_l)?,
+        means_u: read_image(source, &names.means_u)?,
+    };
+
+    let (mins, maxs) = bounds(&meta_json.scales, 3, "scales")?;
+    let scales = ScalesV1 {
+        mins: Vector3::try_from(mins).map_err(in_field("scales.mins"))?,
+        maxs: Vector3::try_from(maxs).map_err(in_field("scales.maxs"))?,
+        scales: read_image(source, &names.scales)?,
+    };
+
+    match meta_json.quats.encoding.as_deref() {
+        None | Some("quaternion_packed") => {}
+        Some(encoding) => {
+            return Err(ParseError::InvalidMetaJson(format!(
+                "unsupported quats encoding: {}",
+                encoding
+            )));
+        }
+    }
+    let quats = Quats(read_image(source, &names.quats)?);
+
+    let (mins, maxs) = bounds(&meta_json.sh0, 4, "sh0")?;
+    let sh_0 = Sh0V1 {
+        mins: [mins[0], mins[1], mins[2], mins[3]],
+        maxs: [maxs[0], maxs[1], maxs[2], maxs[3]],
+        sh_0: read_image(source, &names.sh_0)?,
+    };
+
+    let sh_n = if let Some(sh_n) = &meta_json.sh_n {
+        let bands = sh_bands_v1(&sh_n.shape).ok_or_else(|| {
+            ParseError::InvalidMetaJson(format!("invalid shN shape: {:?}", sh_n.shape))
+        })?;
+        let (mins, maxs) = bounds(sh_n, 1, "shN")?;
+        names.sh_n_centroids = file_name(sh_n, 0, "centroids")?.to_string();
+        names.sh_n_labels = file_name(sh_n, 1, "labels")?.to_string();
+        Some(ShNV1 {
+            bands,
+            mins: mins[0],
+            maxs: maxs[0],
+            centroids: read_image(source, &names.sh_n_centroids)?,
+            labels: read_image(source, &names.sh_n_labels)?,
+        })
+    } else {
+        None
+    };
+
+    Ok(SogDataV1 {
+        count,
+        means,
+        scales,
+        quats,
+        sh_0,
+        sh_n,
+        names: Some(names),
+    })
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + t * (b - a)
+}
+
+#[allow(clippy::identity_op)]
+fn decode_scales_v1(scales: &ScalesV1, count: usize) -> DecodeResult<Vec<f32>> {
+    let ScalesV1 { mins, maxs, scales } = scales;
+
+    let image = decode_webp(scales, "scales")?;
+    image.check_pixel_count(count, "scales")?;
+    let pixels = image.pixels;
+
+    let mut scales = vec![0f32; count * 3];
+    for_each_splat(&mut scales, 3, |i, scale| {
+        scale[0] = lerp(mins.x, maxs.x, pixels[i * 4 + 0] as f32 / 255.0);
+        scale[1] = lerp(mins.y, maxs.y, pixels[i * 4 + 1] as f32 / 255.0);
+        scale[2] = lerp(mins.z, maxs.z, pixels[i * 4 + 2] as f32 / 255.0);
+        Ok(())
+    })?;
+
+    Ok(scales)
+}
+
+fn decode_sh_0_v1(sh0: &Sh0V1, count: usize) -> DecodeResult<Vec<f32>> {
+    let Sh0V1 { mins, maxs, sh_0 } = sh0;
+
+    let image = decode_webp(sh_0, "sh0")?;
+    image.check_pixel_count(count, "sh0")?;
+    let pixels = image.pixels;
+
+    // opacity is quantized as a logit like the colors, so no sigmoid_inv here
+    let mut colors = vec![0f32; count * 4];
+    for_each_splat(&mut colors, 4, |i, color| {
+        for c in 0..4 {
+            color[c] = lerp(mins[c], maxs[c], pixels[i * 4 + c] as f32 / 255.0);
+        }
+        Ok(())
+    })?;
+
+    Ok(colors)
+}
+
+#[allow(clippy::identity_op)]
+fn decode_sh_n_v1(sh_n: &ShNV1, count: usize, max_bands: usize) -> DecodeResult<Vec<f32>> {
+    let ShNV1 {
+        bands,
+        mins,
+        maxs,
+        labels,
+        centroids,
+    } = sh_n;
+
+    let coeff_count = sh_n_coeff_count(*bands)?;
+    let out_coeff_count = sh_coeff_count((*bands as usize).min(max_bands));
+
+    let (centroids, labels) = join(
+        || decode_webp(centroids, "shN_centroids"),
+        || decode_webp(labels, "shN_labels"),
+    );
+    let centroids = centroids?;
+    let palette_count = palette_count_v1(centroids.pixel_count(), coeff_count);
+    let centroids_pixels = centroids.pixels;
+    let labels = labels?;
+    labels.check_pixel_count(count, "shN_labels")?;
+    let labels_pixels = labels.pixels;
+
+    let sh_n_len = count.checked_mul(out_coeff_count * 3).ok_or_else(|| {
+        DecodeError::InvalidSize(format!("too many shN coefficients for count {}", count))
+    })?;
+    let mut sh_n_s = vec![0f32; sh_n_len];
+    for_each_splat(&mut sh_n_s, out_coeff_count * 3, |splat_index, sh_n| {
+        let palette_index = ((labels_pixels[splat_index * 4 + 0] as u16)
+            | ((labels_pixels[splat_index * 4 + 1] as u16) << 8))
+            as usize;
+
+        if palette_index >= palette_count {
+            return Err(DecodeError::ShNLabelOutOfRange {
+                splat_index,
+                entry: None,
+                label: palette_index,
+                palette_count,
+            });
+        }
+
+        for i in 0..3 {
+            for coeff_index in 0..out_coeff_count {
+                let index = i * out_coeff_count + coeff_index;
+                let index2 = (palette_index * coeff_count + coeff_index) * 4 + i;
+                sh_n[index] = lerp(*mins, *maxs, centroids_pixels[index2] as f32 / 255.0);
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(sh_n_s)
+}
+
+/// Decode v1 data with [`decode_splat`].
+pub(crate) fn decode_v1(
+    sog_data: &SogDataV1,
+    options: &DecodeOptions,
+    warnings: Option<&mut Vec<Finding>>,
+) -> Result<Splat> {
+    let SogDataV1 {
+        means,
+        quats,
+        scales,
+        sh_0,
+        sh_n,
+        ..
+    } = sog_data;
+    let count = sog_data.count as usize;
+
+    let decoders = AttributeDecoders {
+        position: || decode_positions(means, count, Vec::new()),
+        rotation: |lenient| decode_rotations(quats, count, lenient, Vec::new()),
+        scale: || decode_scales_v1(scales, count),
+        sh_0: || decode_sh_0_v1(sh_0, count),
+        sh_n: sh_n.as_ref().map(|sh_n| {
+            let decode = move |bands| decode_sh_n_v1(sh_n, count, bands);
+            (sh_n.bands as usize, decode)
+        }),
+    };
+    decode_splat(
+        decoders,
+        count,
+        false,
+        sog_data.names.as_ref(),
+        options,
+        warnings,
+    )
+}
@@ -1,6 +1,46 @@
+mod coordinate;
 mod decode;
-mod metajson;
+mod encode;
+#[cfg(feature = "f16")]
+mod half_float;
+mod image;
+mod inspect;
+mod kernels;
+mod layout;
+mod legacy;
+mod limits;
+mod pack;
+mod parallel;
+mod rotation;
+mod source;
+mod validate;
 
 pub mod error;
+pub mod metajson;
 pub mod types;
-pub use decode::{decode, unpack};
+pub use coordinate::{CoordinateSystem, Direction};
+pub use decode::{
+    DecodeOptions, SplatBuffers, decode, decode_into, decode_into_with, decode_lenient,
+    decode_quantized, decode_v2, decode_v2_with, decode_with, from_files, from_files_with_limits,
+    unpack, unpack_dir, unpack_dir_with_limits, unpack_file, unpack_file_with_limits,
+    unpack_lenient, unpack_lenient_with_limits, unpack_reader, unpack_reader_with_limits,
+    unpack_ref, unpack_ref_with_limits, unpack_source, unpack_source_with_limits,
+    unpack_with_limits,
+};
+pub use encode::{EncodeOptions, encode};
+pub use error::{Limit, LimitExceeded};
+#[cfg(feature = "f16")]
+pub use half;
+#[cfg(feature = "f16")]
+pub use half_float::{Positions, SplatF16, decode_f16};
+pub use inspect::{Bounds, EntryInfo, ImageInfo, SogInfo, inspect, inspect_with_limits};
+pub use layout::{
+    Attribute, ComponentType, InterleavedLayout, LayoutElement, decode_interleaved,
+    decode_interleaved_into,
+};
+pub use limits::Limits;
+pub use metajson::SogMeta;
+pub use pack::{Compression, EntryNames, PackOptions, SogEntry, pack};
+pub use rotation::QuaternionOrder;
+pub use source::{DirSource, SogSource, ZipSource};
+pub use validate::{Finding, Location, Severity, ValidationReport, validate, validate_with_limits};
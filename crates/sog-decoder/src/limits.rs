@@ -0,0 +1,101 @@
+use crate::error::{Limit, LimitExceeded, ParseResult};
+use serde_json::Value;
+
+/// Resource limits for untrusted archives, e.g. user uploads.
+///
+/// Limits are checked before the memory is allocated: entry counts and splat counts come from
+/// the zip directory and `meta.json`, image sizes from the WebP headers, and decompression
+/// stops as soon as the total exceeds the limit, whatever sizes the archive declares.
+/// The default has no limits; set them with the `with_*` methods:
+///
+/// ```
+/// # use sog_decoder::Limits;
+/// let limits = Limits::NONE
+///     .with_max_splats(10_000_000)
+///     .with_max_decompressed_bytes(1 << 30);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Limits {
+    /// Entries in the zip archive, including directories and unreferenced entries.
+    pub max_entries: usize,
+    /// Total decompressed size of the entries read.
+    pub max_decompressed_bytes: u64,
+    /// Splat count of `meta.json`.
+    pub max_splats: usize,
+    /// Pixels of every WebP image.
+    pub max_image_pixels: u64,
+}
+
+impl Limits {
+    pub const NONE: Self = Self {
+        max_entries: usize::MAX,
+        max_decompressed_bytes: u64::MAX,
+        max_splats: usize::MAX,
+        max_image_pixels: u64::MAX,
+    };
+
+    /// Set [`Limits::max_entries`].
+    pub const fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Set [`Limits::max_decompressed_bytes`].
+    pub const fn with_max_decompressed_bytes(mut self, max_decompressed_bytes: u64) -> Self {
+        self.max_decompressed_bytes = max_decompressed_bytes;
+        self
+    }
+
+    /// Set [`Limits::max_splats`].
+    pub const fn with_max_splats(mut self, max_splats: usize) -> Self {
+        self.max_splats = max_splats;
+        self
+    }
+
+    /// Set [`Limits::max_image_pixels`].
+    pub const fn with_max_image_pixels(mut self, max_image_pixels: u64) -> Self {
+        self.max_image_pixels = max_image_pixels;
+        self
+    }
+
+    /// Check the splat count of `meta.json`, taken from the means shape for legacy SOGS.
+    pub(crate) fn check_splat_count(&self, meta_json: &Value) -> ParseResult<()> {
+        let count = meta_json
+            .get("count")
+            .or_else(|| meta_json.pointer("/means/shape/0"))
+            .and_then(|v| v.as_u64());
+        if let Some(count) = count {
+            Self::check(
+                Limit::Splats,
+                count,
+                self.max_splats as u64,
+                Some("meta.json"),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check(
+        limit: Limit,
+        actual: u64,
+        max: u64,
+        entry: Option<&str>,
+    ) -> core::result::Result<(), LimitExceeded> {
+        if actual > max {
+            return Err(LimitExceeded {
+                limit,
+                actual,
+                max,
+                entry: entry.map(str::to_string),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
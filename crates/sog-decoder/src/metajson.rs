@@ -1,46 +1,141 @@
-﻿use serde::Deserialize;
+﻿use crate::error::{ParseError, ParseResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
-#[derive(Debug, Deserialize)]
-pub(crate) struct MetaJsonType {
+/// Unrecognised keys, kept so that a read-modify-write cycle preserves them.
+pub type Extra = Map<String, Value>;
+
+/// Format version of a `meta.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Version {
+    /// Legacy PlayCanvas SOGS, read as [`SogMetaV1`].
+    V1,
+    /// Read as [`SogMeta`].
+    V2,
+}
+
+/// Version of a `meta.json`. Legacy SOGS `meta.json` has no version field,
+/// so without one it is recognised by the `shape` of its attributes.
+pub(crate) fn detect_version(meta_json: &Value) -> ParseResult<Version> {
+    match meta_json.get("version").map(|v| v.as_i64()) {
+        Some(Some(1)) => Ok(Version::V1),
+        Some(Some(2)) => Ok(Version::V2),
+        Some(_) => Err(ParseError::InvalidMetaJson(format!(
+            "unsupported version: {}",
+            meta_json["version"]
+        ))),
+        None if meta_json.pointer("/means/shape").is_some() => Ok(Version::V1),
+        None => Err(ParseError::InvalidMetaJson(
+            "unknown meta.json version: no version field and no v1 attribute shape".to_string(),
+        )),
+    }
+}
+
+/// Contents of `meta.json`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SogMeta {
     pub version: i32,
     pub count: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub antialias: Option<bool>,
-    pub means: Means,
-    pub scales: Scales,
-    pub quats: Quats,
-    pub sh0: Sh0,
-    #[serde(rename(deserialize = "shN"))]
-    pub sh_n: Option<ShN>,
+    pub means: MeansMeta,
+    pub scales: ScalesMeta,
+    pub quats: QuatsMeta,
+    pub sh0: Sh0Meta,
+    #[serde(rename = "shN", skip_serializing_if = "Option::is_none")]
+    pub sh_n: Option<ShNMeta>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub(crate) struct Means {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MeansMeta {
     pub mins: Vec<f32>,
     pub maxs: Vec<f32>,
     pub files: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub(crate) struct Scales {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ScalesMeta {
     pub codebook: Vec<f32>,
     pub files: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub(crate) struct Quats {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuatsMeta {
     pub files: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub(crate) struct Sh0 {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Sh0Meta {
     pub codebook: Vec<f32>,
     pub files: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-pub(crate) struct ShN {
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShNMeta {
     pub count: i32,
     pub bands: i32,
     pub codebook: Vec<f32>,
     pub files: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// Contents of a legacy SOG v1 (PlayCanvas SOGS) `meta.json`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SogMetaV1 {
+    pub means: AttributeMetaV1,
+    pub scales: AttributeMetaV1,
+    pub quats: AttributeMetaV1,
+    pub sh0: AttributeMetaV1,
+    #[serde(rename = "shN", skip_serializing_if = "Option::is_none")]
+    pub sh_n: Option<AttributeMetaV1>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AttributeMetaV1 {
+    pub shape: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dtype: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mins: Option<Bound>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maxs: Option<Bound>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quantization: Option<u32>,
+    pub files: Vec<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `mins`/`maxs` of a v1 attribute: one value per channel, or one for all channels.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Bound {
+    Scalar(f32),
+    Vector(Vec<f32>),
+}
+
+impl Bound {
+    /// Values of the first `len` channels.
+    pub fn to_vec(&self, len: usize) -> Option<Vec<f32>> {
+        match self {
+            Bound::Scalar(v) => Some(vec![*v; len]),
+            Bound::Vector(v) if v.len() >= len => Some(v[..len].to_vec()),
+            Bound::Vector(_) => None,
+        }
+    }
 }
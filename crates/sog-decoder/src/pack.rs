@@ -0,0 +1,267 @@
+use crate::error::{EncodeError, EncodeResult, Result};
+use crate::metajson::{Extra, MeansMeta, QuatsMeta, ScalesMeta, Sh0Meta, ShNMeta, SogMeta};
+use crate::types::{Means, SogDataV2};
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+pub(crate) const META_JSON_FILE: &str = "meta.json";
+
+/// An entry of a bundled `.sog` archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SogEntry {
+    MetaJson,
+    MeansL,
+    MeansU,
+    Scales,
+    Quats,
+    Sh0,
+    ShNCentroids,
+    ShNLabels,
+}
+
+impl SogEntry {
+    /// All entries in the default archive order.
+    pub const ALL: [SogEntry; 8] = [
+        SogEntry::MetaJson,
+        SogEntry::MeansL,
+        SogEntry::MeansU,
+        SogEntry::Scales,
+        SogEntry::Quats,
+        SogEntry::Sh0,
+        SogEntry::ShNCentroids,
+        SogEntry::ShNLabels,
+    ];
+}
+
+/// Archive entry names of the images. These are the names referenced from `meta.json`.
+#[derive(Debug, Clone)]
+pub struct EntryNames {
+    pub means_l: String,
+    pub means_u: String,
+    pub scales: String,
+    pub quats: String,
+    pub sh_0: String,
+    pub sh_n_centroids: String,
+    pub sh_n_labels: String,
+}
+
+impl Default for EntryNames {
+    fn default() -> Self {
+        Self {
+            means_l: "means_l.webp".to_string(),
+            means_u: "means_u.webp".to_string(),
+            scales: "scales.webp".to_string(),
+            quats: "quats.webp".to_string(),
+            sh_0: "sh0.webp".to_string(),
+            sh_n_centroids: "shN_centroids.webp".to_string(),
+            sh_n_labels: "shN_labels.webp".to_string(),
+        }
+    }
+}
+
+impl EntryNames {
+    fn get(&self, entry: SogEntry) -> &str {
+        match entry {
+            SogEntry::MetaJson => META_JSON_FILE,
+            SogEntry::MeansL => &self.means_l,
+            SogEntry::MeansU => &self.means_u,
+            SogEntry::Scales => &self.scales,
+            SogEntry::Quats => &self.quats,
+            SogEntry::Sh0 => &self.sh_0,
+            SogEntry::ShNCentroids => &self.sh_n_centroids,
+            SogEntry::ShNLabels => &self.sh_n_labels,
+        }
+    }
+
+    /// Entry name of an image named after its `meta.json` field, e.g. `"means_l"`.
+    pub(crate) fn image(&self, image: &str) -> Option<&str> {
+        let entry = match image {
+            "means_l" => SogEntry::MeansL,
+            "means_u" => SogEntry::MeansU,
+            "scales" => SogEntry::Scales,
+            "quats" => SogEntry::Quats,
+            "sh0" => SogEntry::Sh0,
+            "shN_centroids" => SogEntry::ShNCentroids,
+            "shN_labels" => SogEntry::ShNLabels,
+            _ => return None,
+        };
+        Some(self.get(entry))
+    }
+}
+
+/// How an archive entry is compressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Stored,
+    /// Deflate with the given level, or the default level if `None`.
+    Deflated(Option<i64>),
+}
+
+/// Options for [`SogDataV2::to_sog_bytes_with`].
+#[derive(Debug, Clone)]
+pub struct PackOptions {
+    /// Entry names of the images. `None` keeps the names the data was unpacked with,
+    /// or uses the default names for data without any.
+    pub names: Option<EntryNames>,
+    /// Order of the entries in the archive.
+    /// Entries missing from the list are appended in the default order.
+    pub order: Vec<SogEntry>,
+    /// Compression of `meta.json`.
+    pub meta_json_compression: Compression,
+    /// Compression of the WebP images. They are already compressed, so they are stored by default.
+    pub image_compression: Compression,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        Self {
+            names: None,
+            order: SogEntry::ALL.to_vec(),
+            meta_json_compression: Compression::Deflated(None),
+            image_compression: Compression::Stored,
+        }
+    }
+}
+
+fn meta_json<I>(sog_data: &SogDataV2<I>, names: &EntryNames) -> SogMeta {
+    let Means { mins, maxs, .. } = &sog_data.means;
+    SogMeta {
+        version: 2,
+        count: sog_data.count,
+        antialias: Some(sog_data.antialias),
+        means: MeansMeta {
+            mins: vec![mins.x, mins.y, mins.z],
+            maxs: vec![maxs.x, maxs.y, maxs.z],
+            files: vec![names.means_l.clone(), names.means_u.clone()],
+            extra: Extra::new(),
+        },
+        scales: ScalesMeta {
+            codebook: sog_data.scales.codebook.0.to_vec(),
+            files: vec![names.scales.clone()],
+            extra: Extra::new(),
+        },
+        quats: QuatsMeta {
+            files: vec![names.quats.clone()],
+            extra: Extra::new(),
+        },
+        sh0: Sh0Meta {
+            codebook: sog_data.sh_0.codebook.0.to_vec(),
+            files: vec![names.sh_0.clone()],
+            extra: Extra::new(),
+        },
+        sh_n: sog_data.sh_n.as_ref().map(|sh_n| ShNMeta {
+            count: sh_n.count,
+            bands: sh_n.bands,
+            codebook: sh_n.codebook.0.to_vec(),
+            files: vec![names.sh_n_centroids.clone(), names.sh_n_labels.clone()],
+            extra: Extra::new(),
+        }),
+        extra: Extra::new(),
+    }
+}
+
+fn check_names<I>(sog_data: &SogDataV2<I>, names: &EntryNames) -> EncodeResult<()> {
+    let mut seen = HashSet::new();
+    for entry in SogEntry::ALL {
+        let is_sh_n = matches!(entry, SogEntry::ShNCentroids | SogEntry::ShNLabels);
+        if is_sh_n && sog_data.sh_n.is_none() {
+            continue;
+        }
+        let name = names.get(entry);
+        if name.is_empty() {
+            return Err(EncodeError::InvalidData(format!(
+                "empty entry name for {:?}",
+                entry
+            )));
+        }
+        if !seen.insert(name) {
+            return Err(EncodeError::InvalidData(format!(
+                "duplicated entry name: {}",
+                name
+            )));
+        }
+    }
+    Ok(())
+}
+
+impl<I: AsRef<[u8]>> SogDataV2<I> {
+    /// Write the images as they are into a bundled `.sog` archive with a regenerated `meta.json`.
+    pub fn to_sog_bytes(&self) -> Result<Vec<u8>> {
+        self.to_sog_bytes_with(&PackOptions::default())
+    }
+
+    /// Same as [`SogDataV2::to_sog_bytes`] with control over entry names, order and compression.
+    pub fn to_sog_bytes_with(&self, options: &PackOptions) -> Result<Vec<u8>> {
+        let PackOptions {
+            names,
+            order,
+            meta_json_compression,
+            image_compression,
+        } = options;
+
+        let default_names = EntryNames::default();
+        let names = names
+            .as_ref()
+            .or(self.names.as_ref())
+            .unwrap_or(&default_names);
+        check_names(self, names)?;
+
+        let meta = serde_json::to_vec(&meta_json(self, names)).map_err(EncodeError::from)?;
+
+        let mut entries = Vec::with_capacity(SogEntry::ALL.len());
+        for entry in order.iter().chain(SogEntry::ALL.iter()) {
+            if !entries.contains(entry) {
+                entries.push(*entry);
+            }
+        }
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for entry in entries {
+            let data = match entry {
+                SogEntry::MetaJson => meta.as_slice(),
+                SogEntry::MeansL => self.means.means_l.as_ref(),
+                SogEntry::MeansU => self.means.means_u.as_ref(),
+                SogEntry::Scales => self.scales.scales.as_ref(),
+                SogEntry::Quats => self.quats.0.as_ref(),
+                SogEntry::Sh0 => self.sh_0.sh_0.as_ref(),
+                SogEntry::ShNCentroids => match &self.sh_n {
+                    Some(sh_n) => sh_n.centroids.as_ref(),
+                    None => continue,
+                },
+                SogEntry::ShNLabels => match &self.sh_n {
+                    Some(sh_n) => sh_n.labels.as_ref(),
+                    None => continue,
+                },
+            };
+
+            let compression = match entry {
+                SogEntry::MetaJson => meta_json_compression,
+                _ => image_compression,
+            };
+            let file_options = match compression {
+                Compression::Stored => {
+                    SimpleFileOptions::default().compression_method(CompressionMethod::Stored)
+                }
+                Compression::Deflated(level) => SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .compression_level(*level),
+            };
+
+            zip.start_file(names.get(entry), file_options)
+                .map_err(EncodeError::from)?;
+            zip.write_all(data)
+                .map_err(|e| EncodeError::from(ZipError::Io(e)))?;
+        }
+        let cursor = zip.finish().map_err(EncodeError::from)?;
+
+        Ok(cursor.into_inner())
+    }
+}
+
+/// Write SOG v2 data into a bundled `.sog` zip archive.
+pub fn pack<I: AsRef<[u8]>>(sog_data: &SogDataV2<I>) -> Result<Vec<u8>> {
+    sog_data.to_sog_bytes()
+}
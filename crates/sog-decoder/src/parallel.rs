@@ -0,0 +1,140 @@
+use crate::error::DecodeResult;
+
+/// Splats per task. Smaller tasks cost more to schedule than they save.
+#[cfg(feature = "rayon")]
+const MIN_SPLATS_PER_TASK: usize = 4096;
+
+/// Run `a` and `b` concurrently with the `rayon` feature, one after another otherwise.
+pub(crate) fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    #[cfg(feature = "rayon")]
+    {
+        rayon::join(a, b)
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        (a(), b())
+    }
+}
+
+/// Call `f` with the index and the `stride` floats of every splat in `out`,
+/// split into chunks across threads with the `rayon` feature.
+/// The error of the lowest failing splat is returned whichever chunk fails first.
+pub(crate) fn for_each_splat<F>(out: &mut [f32], stride: usize, f: F) -> DecodeResult<()>
+where
+    F: Fn(usize, &mut [f32]) -> DecodeResult<()> + Send + Sync,
+{
+    if stride == 0 {
+        return Ok(());
+    }
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        out.par_chunks_mut(stride * MIN_SPLATS_PER_TASK)
+            .enumerate()
+            .map(|(chunk, splats)| {
+                let first = chunk * MIN_SPLATS_PER_TASK;
+                splats
+                    .chunks_exact_mut(stride)
+                    .enumerate()
+                    .try_for_each(|(i, splat)| f(first + i, splat))
+            })
+            .find_first(Result::is_err)
+            .unwrap_or(Ok(()))
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        out.chunks_exact_mut(stride)
+            .enumerate()
+            .try_for_each(|(i, splat)| f(i, splat))
+    }
+}
+
+/// Call `f` with the index of the first splat and the floats of up to `block` splats at a time,
+/// for kernels that work on several splats at once. Errors are reported as by
+/// [`for_each_splat`].
+pub(crate) fn for_each_block<F>(
+    out: &mut [f32],
+    stride: usize,
+    block: usize,
+    f: F,
+) -> DecodeResult<()>
+where
+    F: Fn(usize, &mut [f32]) -> DecodeResult<()> + Send + Sync,
+{
+    if stride == 0 {
+        return Ok(());
+    }
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        out.par_chunks_mut(stride * block)
+            .with_min_len(MIN_SPLATS_PER_TASK / block)
+            .enumerate()
+            .map(|(i, splats)| f(i * block, splats))
+            .find_first(Result::is_err)
+            .unwrap_or(Ok(()))
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        out.chunks_mut(stride * block)
+            .enumerate()
+            .try_for_each(|(i, splats)| f(i * block, splats))
+    }
+}
+
+/// Call `f` with up to `block` splats of `input` and their entries of `state` at a time,
+/// split across threads with the `rayon` feature.
+pub(crate) fn for_each_block_with<T, F>(
+    input: &[f32],
+    stride: usize,
+    state: &mut [T],
+    block: usize,
+    f: F,
+) where
+    T: Send,
+    F: Fn(&[f32], &mut [T]) + Send + Sync,
+{
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        state
+            .par_chunks_mut(block)
+            .zip(input.par_chunks(stride * block))
+            .for_each(|(state, input)| f(input, state));
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        state
+            .chunks_mut(block)
+            .zip(input.chunks(stride * block))
+            .for_each(|(state, input)| f(input, state));
+    }
+}
+
+/// Map the `stride` floats of every splat in `input` to one value of `out`,
+/// split into chunks across threads with the `rayon` feature.
+pub(crate) fn map_splats<T, F>(input: &[f32], stride: usize, out: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(&[f32]) -> T + Send + Sync,
+{
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        out.par_iter_mut()
+            .zip(input.par_chunks_exact(stride))
+            .for_each(|(out, splat)| *out = f(splat));
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        out.iter_mut()
+            .zip(input.chunks_exact(stride))
+            .for_each(|(out, splat)| *out = f(splat));
+    }
+}
@@ -0,0 +1,77 @@
+/// Component order of decoded quaternions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuaternionOrder {
+    /// w, x, y, z
+    #[default]
+    Wxyz,
+    /// x, y, z, w
+    Xyzw,
+}
+
+/// Normalise `w, x, y, z` quaternions in place. Zero quaternions become the identity.
+pub(crate) fn normalize_quaternions(rotations: &mut [f32]) {
+    for q in rotations.chunks_exact_mut(4) {
+        let norm = (q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3]).sqrt();
+        if norm > 0.0 {
+            q.iter_mut().for_each(|c| *c /= norm);
+        } else {
+            q.copy_from_slice(&[1.0, 0.0, 0.0, 0.0]);
+        }
+    }
+}
+
+/// Reorder `w, x, y, z` quaternions in place.
+pub(crate) fn reorder_quaternions(rotations: &mut [f32], order: QuaternionOrder) {
+    match order {
+        QuaternionOrder::Wxyz => {}
+        QuaternionOrder::Xyzw => rotations.chunks_exact_mut(4).for_each(|q| q.rotate_left(1)),
+    }
+}
+
+/// Row-major rotation matrix of a `w, x, y, z` quaternion, normalised first.
+fn rotation_matrix(q: &[f32]) -> [f32; 9] {
+    let norm = (q[0] * q[0] + q[1] * q[1] + q[2] * q[2] + q[3] * q[3]).sqrt();
+    let [w, x, y, z] = if norm > 0.0 {
+        [q[0] / norm, q[1] / norm, q[2] / norm, q[3] / norm]
+    } else {
+        [1.0, 0.0, 0.0, 0.0]
+    };
+    [
+        1.0 - 2.0 * (y * y + z * z),
+        2.0 * (x * y - w * z),
+        2.0 * (x * z + w * y),
+        2.0 * (x * y + w * z),
+        1.0 - 2.0 * (x * x + z * z),
+        2.0 * (y * z - w * x),
+        2.0 * (x * z - w * y),
+        2.0 * (y * z + w * x),
+        1.0 - 2.0 * (x * x + y * y),
+    ]
+}
+
+/// Row-major 3x3 rotation matrices of `w, x, y, z` quaternions.
+pub(crate) fn rotation_matrices(rotations: &[f32]) -> Vec<f32> {
+    rotations
+        .chunks_exact(4)
+        .flat_map(rotation_matrix)
+        .collect()
+}
+
+/// 3D covariances `R * S * S^T * R^T` of `w, x, y, z` quaternions and log scales,
+/// as the upper triangle `xx, xy, xz, yy, yz, zz`.
+pub(crate) fn covariances(rotations: &[f32], log_scales: &[f32]) -> Vec<f32> {
+    rotations
+        .chunks_exact(4)
+        .zip(log_scales.chunks_exact(3))
+        .flat_map(|(q, s)| {
+            let r = rotation_matrix(q);
+            let s2 = [(2.0 * s[0]).exp(), (2.0 * s[1]).exp(), (2.0 * s[2]).exp()];
+            let c = |i: usize, j: usize| {
+                r[i * 3] * r[j * 3] * s2[0]
+                    + r[i * 3 + 1] * r[j * 3 + 1] * s2[1]
+                    + r[i * 3 + 2] * r[j * 3 + 2] * s2[2]
+            };
+            [c(0, 0), c(0, 1), c(0, 2), c(1, 1), c(1, 2), c(2, 2)]
+        })
+        .collect()
+}
@@ -0,0 +1,380 @@
+use crate::error::{Limit, LimitExceeded, ParseError, Result, UnzipResult};
+use crate::image::webp_header;
+use crate::limits::Limits;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use zip::result::ZipError;
+use zip::{CompressionMethod, ZipArchive};
+
+const MAX_PREALLOCATION: u64 = 64 * 1024 * 1024;
+
+/// Resolves `meta.json` and the files it references by name.
+pub trait SogSource {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>>;
+
+    /// Size of a file in bytes if it is known without reading the file,
+    /// so that limits are checked before it is read.
+    fn size(&self, _name: &str) -> Option<u64> {
+        None
+    }
+}
+
+impl<T: SogSource + ?Sized> SogSource for &T {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        (**self).read(name)
+    }
+
+    fn size(&self, name: &str) -> Option<u64> {
+        (**self).size(name)
+    }
+}
+
+/// In-memory files keyed by their names.
+impl SogSource for HashMap<String, Vec<u8>> {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        self.get(name)
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, name.to_string()))
+    }
+
+    fn size(&self, name: &str) -> Option<u64> {
+        self.get(name).map(|data| data.len() as u64)
+    }
+}
+
+/// In-memory files owned by the source. Reading a file moves it out instead of copying it;
+/// only files referenced more than once are copied until their last read.
+pub(crate) struct OwnedFiles {
+    files: RefCell<HashMap<String, Vec<u8>>>,
+    reads_left: RefCell<HashMap<String, usize>>,
+}
+
+impl OwnedFiles {
+    pub(crate) fn new(files: HashMap<String, Vec<u8>>) -> Self {
+        Self {
+            files: RefCell::new(files),
+            reads_left: RefCell::default(),
+        }
+    }
+
+    /// Count the references of every `files` list in `meta_json`.
+    pub(crate) fn expect_reads(&self, meta_json: &serde_json::Value) {
+        fn count(value: &serde_json::Value, reads: &mut HashMap<String, usize>) {
+            let serde_json::Value::Object(object) = value else {
+                return;
+            };
+            for (key, value) in object {
+                if key == "files"
+                    && let serde_json::Value::Array(files) = value
+                {
+                    for name in files.iter().filter_map(|file| file.as_str()) {
+                        *reads.entry(name.to_string()).or_default() += 1;
+                    }
+                } else {
+                    count(value, reads);
+                }
+            }
+        }
+        count(meta_json, &mut self.reads_left.borrow_mut());
+    }
+}
+
+impl SogSource for OwnedFiles {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        let not_found = || io::Error::new(io::ErrorKind::NotFound, name.to_string());
+        let mut reads_left = self.reads_left.borrow_mut();
+        let mut files = self.files.borrow_mut();
+        match reads_left.get_mut(name) {
+            Some(reads) if *reads > 1 => {
+                *reads -= 1;
+                files.get(name).cloned().ok_or_else(not_found)
+            }
+            _ => files.remove(name).ok_or_else(not_found),
+        }
+    }
+
+    fn size(&self, name: &str) -> Option<u64> {
+        self.files.borrow().get(name).map(|data| data.len() as u64)
+    }
+}
+
+/// Enforces the limits on decompressed bytes and image pixels for the files read from any
+/// source, counting the bytes read. The size of each file is checked before it is read
+/// where the source knows it, and the data after.
+pub(crate) struct LimitedSource<S> {
+    source: S,
+    limits: Limits,
+    read: Cell<u64>,
+}
+
+impl<S: SogSource> LimitedSource<S> {
+    pub(crate) fn new(source: S, limits: Limits) -> Self {
+        Self {
+            source,
+            limits,
+            read: Cell::new(0),
+        }
+    }
+
+    pub(crate) fn inner(&self) -> &S {
+        &self.source
+    }
+
+    fn check_read(&self, name: &str, size: u64) -> io::Result<u64> {
+        let read = self.read.get().saturating_add(size);
+        Limits::check(
+            Limit::DecompressedBytes,
+            read,
+            self.limits.max_decompressed_bytes,
+            Some(name),
+        )
+        .map_err(io::Error::other)?;
+        Ok(read)
+    }
+}
+
+impl<S: SogSource> SogSource for LimitedSource<S> {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        if let Some(size) = self.source.size(name) {
+            self.check_read(name, size)?;
+        }
+        let data = self.source.read(name)?;
+        self.read.set(self.check_read(name, data.len() as u64)?);
+        check_image(&self.limits, name, &data).map_err(io::Error::other)?;
+        Ok(data)
+    }
+
+    fn size(&self, name: &str) -> Option<u64> {
+        self.source.size(name)
+    }
+}
+
+/// Check the dimensions of a WebP image against the limit of image pixels, so that
+/// decoding never allocates more pixels than allowed. Other data passes; it fails later
+/// when it is decoded.
+fn check_image(
+    limits: &Limits,
+    name: &str,
+    data: &[u8],
+) -> core::result::Result<(), LimitExceeded> {
+    match webp_header(data) {
+        Some(header) => Limits::check(
+            Limit::ImagePixels,
+            header.width as u64 * header.height as u64,
+            limits.max_image_pixels,
+            Some(name),
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Entry count from the end of central directory record, or from the ZIP64 one when it does
+/// not fit in 16 bits, so that it can be checked before the directory is parsed.
+/// `None` without a record, in which case opening the archive fails anyway.
+fn entry_count<R: Read + Seek>(reader: &mut R) -> io::Result<Option<u64>> {
+    const EOCD: &[u8] = b"PK\x05\x06";
+    const ZIP64_LOCATOR: &[u8] = b"PK\x06\x07";
+    const ZIP64_EOCD: &[u8] = b"PK\x06\x06";
+
+    // the record is 22 bytes followed by a comment of up to 65535
+    let len = reader.seek(SeekFrom::End(0))?;
+    let tail_start = len.saturating_sub(22 + u16::MAX as u64);
+    let mut tail = Vec::new();
+    reader.seek(SeekFrom::Start(tail_start))?;
+    reader.read_to_end(&mut tail)?;
+    let Some(eocd) = tail.windows(22).rposition(|w| w.starts_with(EOCD)) else {
+        return Ok(None);
+    };
+    let entries = u16::from_le_bytes([tail[eocd + 10], tail[eocd + 11]]);
+    if entries != u16::MAX {
+        return Ok(Some(entries as u64));
+    }
+
+    // the ZIP64 locator precedes the record and points at the ZIP64 record
+    let Some(locator_start) = (tail_start + eocd as u64).checked_sub(20) else {
+        return Ok(Some(entries as u64));
+    };
+    let mut locator = [0u8; 20];
+    reader.seek(SeekFrom::Start(locator_start))?;
+    reader.read_exact(&mut locator)?;
+    if !locator.starts_with(ZIP64_LOCATOR) {
+        return Ok(Some(entries as u64));
+    }
+    let mut record = [0u8; 40];
+    reader.seek(SeekFrom::Start(u64::from_le_bytes(
+        locator[8..16].try_into().unwrap(),
+    )))?;
+    if reader.read_exact(&mut record).is_err() || !record.starts_with(ZIP64_EOCD) {
+        return Ok(None);
+    }
+    Ok(Some(u64::from_le_bytes(record[32..40].try_into().unwrap())))
+}
+
+/// Entries of a zip archive, decompressed on demand.
+pub struct ZipSource<R> {
+    archive: RefCell<ZipArchive<R>>,
+    limits: Limits,
+    decompressed: Cell<u64>,
+}
+
+impl<R: Read + Seek> ZipSource<R> {
+    pub fn new(reader: R) -> UnzipResult<Self> {
+        Ok(Self {
+            archive: RefCell::new(ZipArchive::new(reader)?),
+            limits: Limits::NONE,
+            decompressed: Cell::new(0),
+        })
+    }
+
+    /// Open an archive enforcing `limits`. The entry count is checked before the directory
+    /// is parsed; reads fail with an I/O error wrapping [`LimitExceeded`] once a limit is
+    /// exceeded.
+    pub fn new_with_limits(mut reader: R, limits: Limits) -> Result<Self> {
+        let max_entries = limits.max_entries as u64;
+        if let Some(entries) = entry_count(&mut reader)? {
+            Limits::check(Limit::Entries, entries, max_entries, None).map_err(ParseError::from)?;
+        }
+        let mut source = Self::new(reader)?;
+        // the directory itself may hold more entries than its record claims
+        let entries = source.archive.get_mut().len() as u64;
+        Limits::check(Limit::Entries, entries, max_entries, None).map_err(ParseError::from)?;
+        source.limits = limits;
+        Ok(source)
+    }
+
+    /// Names of all entries, including directories.
+    pub(crate) fn names(&self) -> Vec<String> {
+        self.archive
+            .borrow()
+            .file_names()
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Name, compressed and uncompressed size of every entry, from the zip directory.
+    pub(crate) fn entries(&self) -> UnzipResult<Vec<(String, u64, u64)>> {
+        let mut archive = self.archive.borrow_mut();
+        (0..archive.len())
+            .map(|i| {
+                let zip_file = archive.by_index_raw(i)?;
+                Ok((
+                    zip_file.name().to_string(),
+                    zip_file.compressed_size(),
+                    zip_file.size(),
+                ))
+            })
+            .collect()
+    }
+
+    /// Up to `len` leading bytes of an entry, decompressing no more than that.
+    /// The bytes count against the limit of decompressed bytes.
+    pub(crate) fn read_prefix(&self, name: &str, len: u64) -> io::Result<Vec<u8>> {
+        let mut archive = self.archive.borrow_mut();
+        let zip_file = archive.by_name(name).map_err(|e| match e {
+            ZipError::FileNotFound => io::Error::new(io::ErrorKind::NotFound, name.to_string()),
+            ZipError::Io(e) => e,
+            e => io::Error::other(e),
+        })?;
+        let decompressed = self.decompressed.get();
+        let remaining = self
+            .limits
+            .max_decompressed_bytes
+            .saturating_sub(decompressed);
+        let exceeded = |size: u64| LimitExceeded {
+            limit: Limit::DecompressedBytes,
+            actual: decompressed.saturating_add(size),
+            max: self.limits.max_decompressed_bytes,
+            entry: Some(name.to_string()),
+        };
+        let declared = zip_file.size().min(len);
+        if declared > remaining {
+            return Err(io::Error::other(exceeded(declared)));
+        }
+
+        // the declared size comes from the archive and may lie, so it only hints the capacity
+        // and decompression stops one byte past the remaining budget
+        let capacity = declared.min(MAX_PREALLOCATION) as usize;
+        let mut buf = Vec::with_capacity(capacity);
+        zip_file
+            .take(len.min(remaining.saturating_add(1)))
+            .read_to_end(&mut buf)?;
+        if buf.len() as u64 > remaining {
+            return Err(io::Error::other(exceeded(buf.len() as u64)));
+        }
+        self.decompressed.set(decompressed + buf.len() as u64);
+        Ok(buf)
+    }
+
+    /// Check the dimensions of a WebP image against the limit of image pixels.
+    pub(crate) fn check_image(
+        &self,
+        name: &str,
+        data: &[u8],
+    ) -> core::result::Result<(), LimitExceeded> {
+        check_image(&self.limits, name, data)
+    }
+
+    /// Byte range of an uncompressed, unencrypted entry in the underlying reader.
+    pub(crate) fn stored_range(&self, name: &str) -> Option<Range<usize>> {
+        let mut archive = self.archive.borrow_mut();
+        let zip_file = archive.by_name(name).ok()?;
+        if zip_file.compression() != CompressionMethod::Stored || zip_file.encrypted() {
+            return None;
+        }
+        let start = usize::try_from(zip_file.data_start()).ok()?;
+        let size = usize::try_from(zip_file.compressed_size()).ok()?;
+        Some(start..start.checked_add(size)?)
+    }
+}
+
+impl<R: Read + Seek> SogSource for ZipSource<R> {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        let buf = self.read_prefix(name, u64::MAX)?;
+        self.check_image(name, &buf).map_err(io::Error::other)?;
+        Ok(buf)
+    }
+}
+
+/// Loose files in a directory, as written by unbundled SOG exports.
+pub struct DirSource {
+    root: PathBuf,
+}
+
+impl DirSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl DirSource {
+    fn path(&self, name: &str) -> io::Result<PathBuf> {
+        let path = Path::new(name);
+        if !path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("file name outside the directory: {}", name),
+            ));
+        }
+        Ok(self.root.join(path))
+    }
+}
+
+impl SogSource for DirSource {
+    /// Names come from `meta.json`, so only relative paths inside the directory are read.
+    /// Absolute paths and paths with `..` fail with [`io::ErrorKind::InvalidInput`].
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(name)?)
+    }
+
+    fn size(&self, name: &str) -> Option<u64> {
+        let metadata = fs::metadata(self.path(name).ok()?).ok()?;
+        metadata.is_file().then_some(metadata.len())
+    }
+}
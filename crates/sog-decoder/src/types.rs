@@ -1,4 +1,8 @@
 ﻿use crate::error::ParseError;
+pub use crate::image::RgbaImage;
+use crate::pack::EntryNames;
+use std::borrow::Cow;
+use std::convert::Infallible;
 
 #[derive(Debug, Clone, Default)]
 pub struct Vector3 {
@@ -19,22 +23,149 @@ impl TryFrom<Vec<f32>> for Vector3 {
         if value.len() >= 3 {
             Ok(Self::new(value[0], value[1], value[2]))
         } else {
-            Err(ParseError::ParseVector(
-                "Vector must have at least 3 elements".to_string(),
-            ))
+            Err(ParseError::ParseVector(format!(
+                "expected at least 3 elements, found {}",
+                value.len()
+            )))
         }
     }
 }
 
+/// SOG data of any supported version.
 #[derive(Debug, Clone)]
-pub struct SogDataV2 {
+#[allow(clippy::large_enum_variant)]
+pub enum SogData {
+    V1(SogDataV1),
+    V2(SogDataV2),
+}
+
+impl From<SogDataV1> for SogData {
+    fn from(sog_data: SogDataV1) -> Self {
+        Self::V1(sog_data)
+    }
+}
+
+impl From<SogDataV2> for SogData {
+    fn from(sog_data: SogDataV2) -> Self {
+        Self::V2(sog_data)
+    }
+}
+
+/// SOG v2 data. `I` is the storage of the WebP images.
+#[derive(Debug, Clone)]
+pub struct SogDataV2<I = ImageData> {
     pub count: u32,
     pub antialias: bool,
-    pub means: Means,
-    pub scales: Scales,
-    pub quats: Quats,
-    pub sh_0: Sh0,
-    pub sh_n: Option<ShN>,
+    pub means: Means<I>,
+    pub scales: Scales<I>,
+    pub quats: Quats<I>,
+    pub sh_0: Sh0<I>,
+    pub sh_n: Option<ShN<I>>,
+    /// Archive entries of the images as named in `meta.json`, if they were read from one.
+    /// Decode errors of an image report its entry.
+    pub names: Option<EntryNames>,
+}
+
+/// SOG v2 data whose images may borrow from the archive they were unpacked from.
+pub type SogDataRef<'a> = SogDataV2<Cow<'a, [u8]>>;
+
+/// SOG v2 data with the images decoded to RGBA8 but not dequantized,
+/// together with the bounds and codebooks needed to dequantize them, e.g. in a shader.
+pub type QuantizedSogData = SogDataV2<RgbaImage>;
+
+impl<I> SogDataV2<I> {
+    fn map_images<J>(self, mut f: impl FnMut(I) -> J) -> SogDataV2<J> {
+        match self.try_map_images(|_, image| Ok::<_, Infallible>(f(image))) {
+            Ok(sog_data) => sog_data,
+            Err(e) => match e {},
+        }
+    }
+
+    pub(crate) fn try_map_images<J, E>(
+        self,
+        mut f: impl FnMut(&'static str, I) -> Result<J, E>,
+    ) -> Result<SogDataV2<J>, E> {
+        Ok(SogDataV2 {
+            count: self.count,
+            antialias: self.antialias,
+            means: Means {
+                mins: self.means.mins,
+                maxs: self.means.maxs,
+                means_u: f("means_u", self.means.means_u)?,
+                means_l: f("means_l", self.means.means_l)?,
+            },
+            scales: Scales {
+                codebook: self.scales.codebook,
+                scales: f("scales", self.scales.scales)?,
+            },
+            quats: Quats(f("quats", self.quats.0)?),
+            sh_0: Sh0 {
+                codebook: self.sh_0.codebook,
+                sh_0: f("sh0", self.sh_0.sh_0)?,
+            },
+            sh_n: match self.sh_n {
+                Some(sh_n) => Some(ShN {
+                    count: sh_n.count,
+                    bands: sh_n.bands,
+                    codebook: sh_n.codebook,
+                    labels: f("shN_labels", sh_n.labels)?,
+                    centroids: f("shN_centroids", sh_n.centroids)?,
+                }),
+                None => None,
+            },
+            names: self.names,
+        })
+    }
+}
+
+impl<I: AsRef<[u8]>> SogDataV2<I> {
+    /// Borrow the images without copying them.
+    pub fn as_borrowed(&self) -> SogDataRef<'_> {
+        // only the codebooks and bounds are copied; images are borrowed below
+        let SogDataV2 {
+            means,
+            scales,
+            quats,
+            sh_0,
+            sh_n,
+            names,
+            ..
+        } = self;
+        SogDataRef {
+            count: self.count,
+            antialias: self.antialias,
+            means: Means {
+                mins: means.mins.clone(),
+                maxs: means.maxs.clone(),
+                means_u: Cow::Borrowed(means.means_u.as_ref()),
+                means_l: Cow::Borrowed(means.means_l.as_ref()),
+            },
+            scales: Scales {
+                codebook: scales.codebook.clone(),
+                scales: Cow::Borrowed(scales.scales.as_ref()),
+            },
+            quats: Quats(Cow::Borrowed(quats.0.as_ref())),
+            sh_0: Sh0 {
+                codebook: sh_0.codebook.clone(),
+                sh_0: Cow::Borrowed(sh_0.sh_0.as_ref()),
+            },
+            sh_n: sh_n.as_ref().map(|sh_n| ShN {
+                count: sh_n.count,
+                bands: sh_n.bands,
+                codebook: sh_n.codebook.clone(),
+                labels: Cow::Borrowed(sh_n.labels.as_ref()),
+                centroids: Cow::Borrowed(sh_n.centroids.as_ref()),
+            }),
+            names: names.clone(),
+        }
+    }
+}
+
+impl SogDataRef<'_> {
+    /// Copy borrowed images so that the data no longer borrows the archive.
+    pub fn into_owned(self) -> SogDataV2 {
+        self.map_images(Cow::into_owned)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -48,9 +179,10 @@ impl TryFrom<&[f32]> for Codebook {
             arr.copy_from_slice(&value[..256]);
             Ok(Self(arr))
         } else {
-            Err(ParseError::ParseCodebook(
-                "Codebook must have at least 256 elements".to_string(),
-            ))
+            Err(ParseError::ParseCodebook(format!(
+                "expected at least 256 elements, found {}",
+                value.len()
+            )))
         }
     }
 }
@@ -58,33 +190,70 @@ impl TryFrom<&[f32]> for Codebook {
 pub type ImageData = Vec<u8>;
 
 #[derive(Debug, Clone)]
-pub struct Means {
+pub struct Means<I = ImageData> {
     pub mins: Vector3,
     pub maxs: Vector3,
-    pub means_u: ImageData,
-    pub means_l: ImageData,
+    pub means_u: I,
+    pub means_l: I,
 }
 
 #[derive(Debug, Clone)]
-pub struct Quats(pub ImageData);
+pub struct Quats<I = ImageData>(pub I);
 
 #[derive(Debug, Clone)]
-pub struct Scales {
+pub struct Scales<I = ImageData> {
     pub codebook: Codebook,
-    pub scales: ImageData,
+    pub scales: I,
 }
 
 #[derive(Debug, Clone)]
-pub struct Sh0 {
+pub struct Sh0<I = ImageData> {
     pub codebook: Codebook,
-    pub sh_0: ImageData,
+    pub sh_0: I,
 }
 
 #[derive(Debug, Clone)]
-pub struct ShN {
+pub struct ShN<I = ImageData> {
     pub count: i32,
     pub bands: i32,
     pub codebook: Codebook,
+    pub labels: I,
+    pub centroids: I,
+}
+
+/// Legacy SOG v1 (PlayCanvas SOGS) data.
+/// Attributes are quantized linearly between per-attribute `mins` and `maxs` instead of codebooks.
+#[derive(Debug, Clone)]
+pub struct SogDataV1 {
+    pub count: u32,
+    pub means: Means,
+    pub scales: ScalesV1,
+    pub quats: Quats,
+    pub sh_0: Sh0V1,
+    pub sh_n: Option<ShNV1>,
+    /// Archive entries of the images as named in `meta.json`, if they were read from one.
+    pub names: Option<EntryNames>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScalesV1 {
+    pub mins: Vector3,
+    pub maxs: Vector3,
+    pub scales: ImageData,
+}
+
+#[derive(Debug, Clone)]
+pub struct Sh0V1 {
+    pub mins: [f32; 4],
+    pub maxs: [f32; 4],
+    pub sh_0: ImageData,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShNV1 {
+    pub bands: i32,
+    pub mins: f32,
+    pub maxs: f32,
     pub labels: ImageData,
     pub centroids: ImageData,
 }
@@ -99,4 +268,39 @@ pub struct Splat {
     pub scale: Vec<f32>,
     pub sh_0: Vec<f32>,
     pub sh_n: Option<Vec<f32>>,
+    /// Row-major 3x3 rotation matrices, if requested with `DecodeOptions::rotation_matrix`.
+    pub rotation_matrix: Option<Vec<f32>>,
+    /// 3D covariances `xx, xy, xz, yy, yz, zz`, if requested with `DecodeOptions::covariance`.
+    pub covariance: Option<Vec<f32>>,
+}
+
+/// SH_C0 = Y_0^0 = 1 / (2 * sqrt(pi))
+pub const SH_C0: f32 = 0.282_094_8;
+
+impl Splat {
+    /// Convert the attributes in place from their stored representation into render-ready values:
+    /// linear scales instead of log scales, opacity in 0..1 instead of a logit,
+    /// and RGB base color `SH_C0 * dc + 0.5` instead of raw DC coefficients.
+    /// The color is not clamped. Positions, rotations and shN are unchanged.
+    ///
+    /// The conversion is not idempotent, so activate a splat only once.
+    pub fn activate(&mut self) {
+        activate_scales(&mut self.scale);
+        activate_colors(&mut self.sh_0);
+    }
+}
+
+pub(crate) fn activate_scales(scales: &mut [f32]) {
+    for s in scales.iter_mut() {
+        *s = s.exp();
+    }
+}
+
+pub(crate) fn activate_colors(colors: &mut [f32]) {
+    for c in colors.chunks_exact_mut(4) {
+        c[0] = SH_C0 * c[0] + 0.5;
+        c[1] = SH_C0 * c[1] + 0.5;
+        c[2] = SH_C0 * c[2] + 0.5;
+        c[3] = 1.0 / (1.0 + (-c[3]).exp());
+    }
 }
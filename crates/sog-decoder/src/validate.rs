@@ -0,0 +1,673 @@
+use crate::decode::{
+    check_means_dims, palette_count_v1, read_image, read_meta_json, sh_n_centroid_pixels,
+    sh_n_coeff_count,
+};
+use crate::error::{DecodeError, Error, ParseError};
+use crate::image::{RgbaImage, decode_webp};
+use crate::legacy::sh_bands_v1;
+use crate::limits::Limits;
+use crate::metajson::{Bound, SogMeta, SogMetaV1, Version, detect_version};
+use crate::source::ZipSource;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Cursor;
+
+/// Findings of a per-splat check beyond this are summarised in one finding.
+const MAX_SPLAT_FINDINGS: usize = 16;
+
+/// How bad a [`Finding`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Decoding works, but the data is unusual or wasteful.
+    Warning,
+    /// Decoding fails or produces wrong data.
+    Error,
+}
+
+/// Where a [`Finding`] is. Every part is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Location {
+    /// Name of the archive entry.
+    pub entry: Option<String>,
+    /// `meta.json` field, or the image named after it, e.g. `"means.mins"` or `"shN_labels"`.
+    pub field: Option<String>,
+    pub splat_index: Option<usize>,
+}
+
+/// A problem found by [`validate`].
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Stable identifier of the kind of problem. Problems which make decoding fail share
+    /// the code of the error, see [`Error::code`].
+    pub code: &'static str,
+    pub message: String,
+    pub location: Location,
+}
+
+impl Finding {
+    /// Finding of an error, with the messages of its sources appended.
+    pub(crate) fn from_error(severity: Severity, error: Error) -> Self {
+        let mut message = error.to_string();
+        let mut source = std::error::Error::source(&error);
+        while let Some(cause) = source {
+            message.push_str(&format!(": {}", cause));
+            source = cause.source();
+        }
+        Self {
+            severity,
+            code: error.code(),
+            message,
+            location: Location {
+                entry: error.entry().map(str::to_string),
+                field: error.image().map(str::to_string),
+                splat_index: error.splat_index(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}[{}]", severity, self.code)?;
+        let Location {
+            entry,
+            field,
+            splat_index,
+        } = &self.location;
+        if let Some(entry) = entry {
+            write!(f, " {}", entry)?;
+        }
+        if let Some(field) = field {
+            write!(f, " ({})", field)?;
+        }
+        if let Some(splat_index) = splat_index {
+            write!(f, " splat {}", splat_index)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Every problem [`validate`] found in an archive.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub findings: Vec<Finding>,
+}
+
+impl ValidationReport {
+    /// Whether no finding is an error, i.e. the archive decodes.
+    pub fn is_valid(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == Severity::Warning)
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        code: &'static str,
+        message: impl Into<String>,
+        location: Location,
+    ) {
+        self.findings.push(Finding {
+            severity,
+            code,
+            message: message.into(),
+            location,
+        });
+    }
+
+    fn push_error(&mut self, error: impl Into<Error>, location: Location) {
+        let finding = Finding::from_error(Severity::Error, error.into());
+        self.findings.push(Finding {
+            location: Location {
+                entry: location.entry.or(finding.location.entry),
+                field: location.field.or(finding.location.field),
+                splat_index: location.splat_index.or(finding.location.splat_index),
+            },
+            ..finding
+        });
+    }
+
+    /// Report the first findings of a per-splat check and summarise the rest.
+    fn push_splat_errors(&mut self, mut errors: impl Iterator<Item = DecodeError>, entry: &str) {
+        let Some(first) = errors.next() else {
+            return;
+        };
+        let code = first.code();
+        for error in std::iter::once(first).chain(errors.by_ref().take(MAX_SPLAT_FINDINGS - 1)) {
+            self.push_error(error, entry_location(entry, None));
+        }
+        let more = errors.count();
+        if more > 0 {
+            self.push(
+                Severity::Error,
+                code,
+                format!("{} more splats", more),
+                entry_location(entry, None),
+            );
+        }
+    }
+}
+
+fn field_location(field: &str) -> Location {
+    Location {
+        entry: Some("meta.json".to_string()),
+        field: Some(field.to_string()),
+        splat_index: None,
+    }
+}
+
+fn entry_location(entry: &str, field: Option<&str>) -> Location {
+    Location {
+        entry: Some(entry.to_string()),
+        field: field.map(str::to_string),
+        splat_index: None,
+    }
+}
+
+/// Check a bundled `.sog` archive and collect every problem instead of stopping at the first.
+///
+/// Archives are checked field by field: missing entries, undecodable images, image
+/// dimensions, codebook lengths, bounds, SH bands, quaternion modes, shN labels and entries
+/// which `meta.json` does not reference. Legacy v1 archives have bounds instead of codebooks.
+pub fn validate(file: &[u8]) -> ValidationReport {
+    validate_with_limits(file, &Limits::NONE)
+}
+
+/// Same as [`validate`], enforcing `limits`. An exceeded limit is an error finding, and
+/// checking stops there if it is the entry count, the splat count or `meta.json` itself.
+pub fn validate_with_limits(file: &[u8], limits: &Limits) -> ValidationReport {
+    let mut report = ValidationReport::default();
+
+    let source = match ZipSource::new_with_limits(Cursor::new(file), *limits) {
+        Ok(source) => source,
+        Err(e) => {
+            report.push_error(e, Location::default());
+            return report;
+        }
+    };
+    let meta_json = match read_meta_json(&source) {
+        Ok(meta_json) => meta_json,
+        Err(e) => {
+            report.push_error(e, Location::default());
+            return report;
+        }
+    };
+    if let Err(e) = limits.check_splat_count(&meta_json) {
+        report.push_error(e, Location::default());
+        return report;
+    }
+
+    let version = match detect_version(&meta_json) {
+        Ok(version) => version,
+        Err(e) => {
+            report.push(
+                Severity::Error,
+                "unsupported_version",
+                e.to_string(),
+                field_location("version"),
+            );
+            return report;
+        }
+    };
+    let referenced = match version {
+        Version::V1 => {
+            let meta_json = replace_nulls(meta_json, &NULLABLE_V1, &mut report);
+            match serde_json::from_value::<SogMetaV1>(meta_json) {
+                Ok(meta_json) => validate_v1(&source, &meta_json, &mut report),
+                Err(e) => {
+                    report.push_error(ParseError::DeserializeMetaJson(e), Location::default());
+                    return report;
+                }
+            }
+        }
+        Version::V2 => {
+            match serde_json::from_value::<SogMeta>(replace_nulls(
+                meta_json,
+                &NULLABLE_V2,
+                &mut report,
+            )) {
+                Ok(meta_json) => validate_v2(&source, &meta_json, &mut report),
+                Err(e) => {
+                    report.push_error(ParseError::DeserializeMetaJson(e), Location::default());
+                    return report;
+                }
+            }
+        }
+    };
+
+    for name in source.names() {
+        if name != "meta.json" && !name.ends_with('/') && !referenced.contains(&name) {
+            report.push(
+                Severity::Warning,
+                "unused_entry",
+                "entry is not referenced by meta.json",
+                entry_location(&name, None),
+            );
+        }
+    }
+
+    report
+}
+
+/// Bounds and codebooks of v2 `meta.json`: pointer, field and finding code.
+const NULLABLE_V2: [(&str, &str, &str); 5] = [
+    ("/means/mins", "means.mins", "non_finite_bounds"),
+    ("/means/maxs", "means.maxs", "non_finite_bounds"),
+    ("/scales/codebook", "scales.codebook", "non_finite_codebook"),
+    ("/sh0/codebook", "sh0.codebook", "non_finite_codebook"),
+    ("/shN/codebook", "shN.codebook", "non_finite_codebook"),
+];
+
+/// Bounds of v1 `meta.json`, as in [`NULLABLE_V2`].
+const NULLABLE_V1: [(&str, &str, &str); 8] = [
+    ("/means/mins", "means.mins", "non_finite_bounds"),
+    ("/means/maxs", "means.maxs", "non_finite_bounds"),
+    ("/scales/mins", "scales.mins", "non_finite_bounds"),
+    ("/scales/maxs", "scales.maxs", "non_finite_bounds"),
+    ("/sh0/mins", "sh0.mins", "non_finite_bounds"),
+    ("/sh0/maxs", "sh0.maxs", "non_finite_bounds"),
+    ("/shN/mins", "shN.mins", "non_finite_bounds"),
+    ("/shN/maxs", "shN.maxs", "non_finite_bounds"),
+];
+
+/// JSON has no NaN or infinity, so writers emit `null` for them. Report such values in
+/// the arrays at `nullable` and replace them with zero, so the rest of `meta.json` is still
+/// checked.
+fn replace_nulls(
+    mut meta_json: Value,
+    nullable: &[(&str, &str, &'static str)],
+    report: &mut ValidationReport,
+) -> Value {
+    for &(pointer, field, code) in nullable {
+        let Some(Value::Array(values)) = meta_json.pointer_mut(pointer) else {
+            continue;
+        };
+        let nulls = values.iter().filter(|v| v.is_null()).count();
+        if nulls > 0 {
+            values
+                .iter_mut()
+                .filter(|v| v.is_null())
+                .for_each(|v| *v = Value::from(0.0));
+            report.push(
+                Severity::Error,
+                code,
+                format!("{} values are null, i.e. not finite", nulls),
+                field_location(field),
+            );
+        }
+    }
+    meta_json
+}
+
+/// Check every field of a v1 archive as [`validate_v2`] does. Returns the referenced entries.
+fn validate_v1(
+    source: &ZipSource<Cursor<&[u8]>>,
+    meta_json: &SogMetaV1,
+    report: &mut ValidationReport,
+) -> HashSet<String> {
+    let count = match meta_json.means.shape.first() {
+        Some(&count) if count > u32::MAX as usize => {
+            report.push_error(
+                ParseError::InvalidMetaJson(format!("too many splats: {}", count)),
+                field_location("means.shape"),
+            );
+            count
+        }
+        Some(&count) => count,
+        None => {
+            report.push_error(
+                ParseError::InvalidMetaJson("missing means shape".to_string()),
+                field_location("means.shape"),
+            );
+            0
+        }
+    };
+    if meta_json.means.shape.first() == Some(&0) {
+        report.push(
+            Severity::Warning,
+            "empty_scene",
+            "splat count is 0",
+            field_location("means.shape"),
+        );
+    }
+
+    let mut attributes = vec![
+        ("means", &meta_json.means, 3),
+        ("scales", &meta_json.scales, 3),
+        ("sh0", &meta_json.sh0, 4),
+    ];
+    if let Some(sh_n) = &meta_json.sh_n {
+        attributes.push(("shN", sh_n, 1));
+    }
+    for (field, attribute, len) in attributes {
+        for (bound, name) in [(&attribute.mins, "mins"), (&attribute.maxs, "maxs")] {
+            let field = format!("{}.{}", field, name);
+            match bound.as_ref().and_then(|bound| bound.to_vec(len)) {
+                None => report.push_error(
+                    ParseError::InvalidMetaJson(format!("missing or short {}", field)),
+                    field_location(&field),
+                ),
+                Some(bound) if bound.iter().any(|v| !v.is_finite()) => report.push(
+                    Severity::Error,
+                    "non_finite_bounds",
+                    format!("{:?} has values which are not finite", bound),
+                    field_location(&field),
+                ),
+                Some(_) => {}
+            }
+        }
+    }
+    let bounds = |bound: &Option<Bound>| bound.as_ref().and_then(|bound| bound.to_vec(3));
+    if let (Some(mins), Some(maxs)) = (bounds(&meta_json.means.mins), bounds(&meta_json.means.maxs))
+        && mins.iter().zip(&maxs).any(|(min, max)| min > max)
+    {
+        report.push(
+            Severity::Warning,
+            "inverted_bounds",
+            format!("mins {:?} exceed maxs {:?}", mins, maxs),
+            field_location("means"),
+        );
+    }
+
+    if let Some(encoding) = &meta_json.quats.encoding
+        && encoding != "quaternion_packed"
+    {
+        report.push_error(
+            ParseError::InvalidMetaJson(format!("unsupported quats encoding: {}", encoding)),
+            field_location("quats.encoding"),
+        );
+    }
+
+    let mut files = vec![
+        (
+            "means",
+            &meta_json.means.files,
+            ["means_l", "means_u"].as_slice(),
+        ),
+        ("scales", &meta_json.scales.files, &["scales"]),
+        ("quats", &meta_json.quats.files, &["quats"]),
+        ("sh0", &meta_json.sh0.files, &["sh0"]),
+    ];
+    if let Some(sh_n) = &meta_json.sh_n {
+        files.push(("shN", &sh_n.files, &["shN_centroids", "shN_labels"]));
+    }
+    let (referenced, images) = check_images(source, files, count, report);
+    let image = |name: &str| images.iter().find(|(image, ..)| *image == name);
+
+    if let Some(sh_n) = &meta_json.sh_n {
+        let Some(bands) = sh_bands_v1(&sh_n.shape) else {
+            report.push(
+                Severity::Error,
+                "invalid_sh_bands",
+                format!("invalid shN shape: {:?}", sh_n.shape),
+                field_location("shN.shape"),
+            );
+            return referenced;
+        };
+        if let Some((_, _, centroids)) = image("shN_centroids")
+            && let Some((_, entry, labels)) = image("shN_labels")
+            && let Ok(coeff_count) = sh_n_coeff_count(bands)
+        {
+            let palette_count = palette_count_v1(centroids.pixel_count(), coeff_count);
+            report.push_splat_errors(label_errors(labels, count, palette_count), entry);
+        }
+    }
+
+    referenced
+}
+
+/// Check every field of a v2 archive. Returns the referenced entries.
+fn validate_v2(
+    source: &ZipSource<Cursor<&[u8]>>,
+    meta_json: &SogMeta,
+    report: &mut ValidationReport,
+) -> HashSet<String> {
+    let count = meta_json.count as usize;
+    if count == 0 {
+        report.push(
+            Severity::Warning,
+            "empty_scene",
+            "count is 0",
+            field_location("count"),
+        );
+    }
+
+    for (field, bound) in [
+        ("means.mins", &meta_json.means.mins),
+        ("means.maxs", &meta_json.means.maxs),
+    ] {
+        if bound.len() < 3 {
+            report.push_error(
+                ParseError::ParseVector(format!(
+                    "{}: expected at least 3 elements, found {}",
+                    field,
+                    bound.len()
+                )),
+                field_location(field),
+            );
+        } else if bound.iter().any(|v| !v.is_finite()) {
+            report.push(
+                Severity::Error,
+                "non_finite_bounds",
+                format!("{:?} has values which are not finite", bound),
+                field_location(field),
+            );
+        }
+    }
+    let (mins, maxs) = (&meta_json.means.mins, &meta_json.means.maxs);
+    if mins.iter().zip(maxs).any(|(min, max)| min > max) {
+        report.push(
+            Severity::Warning,
+            "inverted_bounds",
+            format!("mins {:?} exceed maxs {:?}", mins, maxs),
+            field_location("means"),
+        );
+    }
+
+    let mut codebooks = vec![
+        ("scales.codebook", &meta_json.scales.codebook),
+        ("sh0.codebook", &meta_json.sh0.codebook),
+    ];
+    if let Some(sh_n) = &meta_json.sh_n {
+        codebooks.push(("shN.codebook", &sh_n.codebook));
+    }
+    for (field, codebook) in codebooks {
+        if codebook.len() < 256 {
+            report.push_error(
+                ParseError::ParseCodebook(format!(
+                    "{}: expected at least 256 elements, found {}",
+                    field,
+                    codebook.len()
+                )),
+                field_location(field),
+            );
+        } else if codebook.len() > 256 {
+            report.push(
+                Severity::Warning,
+                "invalid_codebook",
+                format!("{} elements, only the first 256 are used", codebook.len()),
+                field_location(field),
+            );
+        }
+        if codebook.iter().any(|v| !v.is_finite()) {
+            report.push(
+                Severity::Error,
+                "non_finite_codebook",
+                "codebook has values which are not finite",
+                field_location(field),
+            );
+        }
+    }
+
+    let mut files = vec![
+        (
+            "means",
+            &meta_json.means.files,
+            ["means_l", "means_u"].as_slice(),
+        ),
+        ("scales", &meta_json.scales.files, &["scales"]),
+        ("quats", &meta_json.quats.files, &["quats"]),
+        ("sh0", &meta_json.sh0.files, &["sh0"]),
+    ];
+    if let Some(sh_n) = &meta_json.sh_n {
+        files.push(("shN", &sh_n.files, &["shN_centroids", "shN_labels"]));
+    }
+    let (referenced, images) = check_images(source, files, count, report);
+    let image = |name: &str| images.iter().find(|(image, ..)| *image == name);
+
+    if let Some(sh_n) = &meta_json.sh_n {
+        let coeff_count = sh_n_coeff_count(sh_n.bands);
+        if let Err(e) = &coeff_count {
+            report.push(
+                Severity::Error,
+                "invalid_sh_bands",
+                e.to_string(),
+                field_location("shN.bands"),
+            );
+        }
+        if sh_n.count <= 0 {
+            report.push(
+                Severity::Error,
+                "invalid_palette_count",
+                format!("invalid shN count: {}", sh_n.count),
+                field_location("shN.count"),
+            );
+        } else if sh_n.count > 65536 {
+            report.push(
+                Severity::Warning,
+                "invalid_palette_count",
+                format!(
+                    "shN count {} exceeds the 65536 entries labels can address",
+                    sh_n.count
+                ),
+                field_location("shN.count"),
+            );
+        }
+        let palette_count = sh_n.count.max(0) as usize;
+        if let Ok(coeff_count) = coeff_count
+            && let Some((name, entry, centroids)) = image("shN_centroids")
+        {
+            let result = sh_n_centroid_pixels(palette_count, coeff_count)
+                .and_then(|expected| centroids.check_pixel_count(expected, name));
+            if let Err(e) = result {
+                report.push_error(e, entry_location(entry, Some(name)));
+            }
+        }
+        if let Some((_, entry, labels)) = image("shN_labels") {
+            report.push_splat_errors(label_errors(labels, count, palette_count), entry);
+        }
+    }
+
+    referenced
+}
+
+/// Decoded images with their `meta.json` name and archive entry.
+type Images<'a> = Vec<(&'static str, &'a str, RgbaImage)>;
+
+/// Read and decode the images of each attribute, given as its field, its `files` and the
+/// names of the images they hold, and run the checks both versions share: image sizes
+/// against the splat count, means_l against means_u and quaternion modes.
+/// Returns the referenced entries and the images which decoded.
+fn check_images<'a>(
+    source: &ZipSource<Cursor<&[u8]>>,
+    files: Vec<(&str, &'a Vec<String>, &[&'static str])>,
+    count: usize,
+    report: &mut ValidationReport,
+) -> (HashSet<String>, Images<'a>) {
+    let mut referenced = HashSet::new();
+    let mut images = Vec::new();
+    for (field, files, names) in files {
+        referenced.extend(files.iter().cloned());
+        for (i, image) in names.iter().enumerate() {
+            let Some(entry) = files.get(i) else {
+                report.push_error(
+                    ParseError::InvalidMetaJson(format!("missing {} file name", image)),
+                    field_location(&format!("{}.files", field)),
+                );
+                continue;
+            };
+            let decoded = read_image(source, entry)
+                .map_err(Error::from)
+                .and_then(|data| Ok(decode_webp(&data, image)?));
+            match decoded {
+                Ok(decoded) => images.push((*image, entry.as_str(), decoded)),
+                Err(e) => report.push_error(e, entry_location(entry, Some(image))),
+            }
+        }
+    }
+    let image = |name: &str| images.iter().find(|(image, ..)| *image == name);
+
+    for (name, entry, decoded) in &images {
+        let expected = match *name {
+            "shN_centroids" => continue,
+            _ => count,
+        };
+        if let Err(e) = decoded.check_pixel_count(expected, name) {
+            report.push_error(e, entry_location(entry, Some(name)));
+        }
+    }
+    if let (Some((_, _, lower)), Some((_, entry, upper))) = (image("means_l"), image("means_u"))
+        && let Err(e) = check_means_dims(lower, upper)
+    {
+        report.push_error(e, entry_location(entry, Some("means_u")));
+    }
+
+    if let Some((_, entry, quats)) = image("quats") {
+        report.push_splat_errors(rotation_mode_errors(quats, count), entry);
+    }
+
+    (referenced, images)
+}
+
+fn rotation_mode_errors(quats: &RgbaImage, count: usize) -> impl Iterator<Item = DecodeError> + '_ {
+    quats
+        .pixels
+        .chunks_exact(4)
+        .take(count)
+        .enumerate()
+        .filter(|(_, pixel)| pixel[3] < 252)
+        .map(|(splat_index, pixel)| DecodeError::InvalidRotationMode {
+            splat_index,
+            entry: None,
+            mode: pixel[3],
+        })
+}
+
+fn label_errors(
+    labels: &RgbaImage,
+    count: usize,
+    palette_count: usize,
+) -> impl Iterator<Item = DecodeError> + '_ {
+    labels
+        .pixels
+        .chunks_exact(4)
+        .take(count)
+        .enumerate()
+        .map(|(splat_index, pixel)| (splat_index, pixel[0] as usize | (pixel[1] as usize) << 8))
+        .filter(move |(_, label)| *label >= palette_count)
+        .map(
+            move |(splat_index, label)| DecodeError::ShNLabelOutOfRange {
+                splat_index,
+                entry: None,
+                label,
+                palette_count,
+            },
+        )
+}
@@ -0,0 +1,208 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use sog_decoder::types::{SogData, SogDataV2, Splat};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const SAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../sample_data/pizza.sog");
+
+/// The sample archive unbundled into loose files.
+pub const SAMPLE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../sample_data/pizza");
+
+pub fn sample_bytes() -> Vec<u8> {
+    std::fs::read(SAMPLE).unwrap()
+}
+
+/// Unpack an archive which must hold v2 data.
+pub fn unpack_v2(file: &[u8]) -> SogDataV2 {
+    match sog_decoder::unpack(file).unwrap() {
+        SogData::V2(sog_data) => sog_data,
+        SogData::V1(_) => panic!("expected v2 data"),
+    }
+}
+
+/// Entries of the sample archive keyed by their names.
+pub fn sample_files() -> HashMap<String, Vec<u8>> {
+    entries(&sample_bytes())
+}
+
+/// Entries of a zip archive keyed by their names.
+pub fn entries(file: &[u8]) -> HashMap<String, Vec<u8>> {
+    let mut archive = ZipArchive::new(Cursor::new(file)).unwrap();
+    (0..archive.len())
+        .map(|i| {
+            let mut entry = archive.by_index(i).unwrap();
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            (entry.name().to_string(), data)
+        })
+        .collect()
+}
+
+/// Assert that two decoded splats have the same attributes.
+pub fn assert_same_splat(a: &Splat, b: &Splat) {
+    assert_eq!(a.count, b.count);
+    assert_eq!(a.sh_degree, b.sh_degree);
+    assert_eq!(a.position, b.position);
+    assert_eq!(a.rotation, b.rotation);
+    assert_eq!(a.scale, b.scale);
+    assert_eq!(a.sh_0, b.sh_0);
+    assert_eq!(a.sh_n, b.sh_n);
+}
+
+pub fn meta_json(files: &HashMap<String, Vec<u8>>) -> serde_json::Value {
+    serde_json::from_slice(&files["meta.json"]).unwrap()
+}
+
+pub fn set_meta_json(files: &mut HashMap<String, Vec<u8>>, meta_json: &serde_json::Value) {
+    files.insert("meta.json".to_string(), meta_json.to_string().into_bytes());
+}
+
+/// Stored zip archive of `files`, in name order.
+pub fn archive(files: &HashMap<String, Vec<u8>>) -> Vec<u8> {
+    let mut names: Vec<_> = files.keys().collect();
+    names.sort();
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for name in names {
+        writer.start_file(name.as_str(), options).unwrap();
+        writer.write_all(&files[name]).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+/// Deflated zip archive of `files`.
+pub fn deflated_archive(files: &HashMap<String, Vec<u8>>) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, data) in files {
+        writer.start_file(name.as_str(), options).unwrap();
+        writer.write_all(data).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+/// Width, height and RGBA pixels of a WebP image.
+pub fn decode_image(webp: &[u8]) -> (u32, u32, Vec<u8>) {
+    let mut decoder = image_webp::WebPDecoder::new(Cursor::new(webp)).unwrap();
+    let (width, height) = decoder.dimensions();
+    let mut pixels = vec![0; decoder.output_buffer_size().unwrap()];
+    decoder.read_image(&mut pixels).unwrap();
+    if !decoder.has_alpha() {
+        pixels = pixels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect();
+    }
+    (width, height, pixels)
+}
+
+/// Lossless WebP image of RGBA pixels.
+pub fn encode_image(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    let mut webp = Vec::new();
+    image_webp::WebPEncoder::new(&mut webp)
+        .encode(pixels, width, height, image_webp::ColorType::Rgba8)
+        .unwrap();
+    webp
+}
+
+/// Apply `edit` to the RGBA pixels of the entry `name`.
+pub fn edit_image(files: &mut HashMap<String, Vec<u8>>, name: &str, edit: impl FnOnce(&mut [u8])) {
+    let (width, height, mut pixels) = decode_image(&files[name]);
+    edit(&mut pixels);
+    files.insert(name.to_string(), encode_image(width, height, &pixels));
+}
+
+/// Name of the `index`th file of the `meta.json` field `field`.
+pub fn file_name(files: &HashMap<String, Vec<u8>>, field: &str, index: usize) -> String {
+    meta_json(files)[field]["files"][index]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Splats of [`v1_files`].
+pub const V1_COUNT: usize = 4;
+
+/// A legacy SOGS v1 archive of four splats in 2x2 images, with one shN band and a palette
+/// of two entries. Rotations are the first splats of the sample.
+pub fn v1_files() -> HashMap<String, Vec<u8>> {
+    let sample = sample_files();
+    let (_, _, quats) = decode_image(&sample[&file_name(&sample, "quats", 0)]);
+
+    let image = |pixels: [[u8; 4]; V1_COUNT]| encode_image(2, 2, pixels.as_flattened());
+    let meta = serde_json::json!({
+        "means": {
+            "shape": [4, 3],
+            "dtype": "float32",
+            "mins": [-1.0, 0.0, 0.5],
+            "maxs": [1.0, 2.0, 0.5],
+            "files": ["means_l.webp", "means_u.webp"],
+        },
+        "scales": {
+            "shape": [4, 3],
+            "dtype": "float32",
+            "mins": [-4.0, -3.0, -2.0],
+            "maxs": [1.0, 2.0, 3.0],
+            "files": ["scales.webp"],
+        },
+        "quats": {
+            "shape": [4, 4],
+            "dtype": "uint8",
+            "encoding": "quaternion_packed",
+            "files": ["quats.webp"],
+        },
+        "sh0": {
+            "shape": [4, 1, 4],
+            "dtype": "float32",
+            "mins": [-1.0, -1.0, -1.0, -5.0],
+            "maxs": [1.0, 1.0, 1.0, 5.0],
+            "files": ["sh0.webp"],
+        },
+        "shN": {
+            "shape": [4, 9],
+            "dtype": "float32",
+            "mins": -0.5,
+            "maxs": 0.5,
+            "quantization": 8,
+            "files": ["shN_centroids.webp", "shN_labels.webp"],
+        },
+    });
+
+    let mut files = HashMap::new();
+    files.insert("meta.json".to_string(), meta.to_string().into_bytes());
+    // positions 0, 65535, 255 and 256 in every channel
+    let means_l = image([[0; 4], [255; 4], [255; 4], [0; 4]]);
+    let means_u = image([[0; 4], [255; 4], [0; 4], [1; 4]]);
+    files.insert("means_l.webp".to_string(), means_l);
+    files.insert("means_u.webp".to_string(), means_u);
+    let scales = image([
+        [0, 0, 0, 255],
+        [255, 255, 255, 255],
+        [51, 102, 153, 255],
+        [0; 4],
+    ]);
+    files.insert("scales.webp".to_string(), scales);
+    let quats: [[u8; 4]; V1_COUNT] =
+        std::array::from_fn(|i| quats[i * 4..][..4].try_into().unwrap());
+    files.insert("quats.webp".to_string(), image(quats));
+    let sh0 = image([[0; 4], [255; 4], [51, 102, 153, 204], [0, 255, 0, 255]]);
+    files.insert("sh0.webp".to_string(), sh0);
+    // palette entry e, coefficient c at pixel e * 3 + c
+    let centroids: Vec<u8> = (0..6 * 4).map(|i| (i * 10) as u8).collect();
+    files.insert(
+        "shN_centroids.webp".to_string(),
+        encode_image(3, 2, &centroids),
+    );
+    let labels = image([
+        [0, 0, 0, 255],
+        [1, 0, 0, 255],
+        [1, 0, 0, 255],
+        [0, 0, 0, 255],
+    ]);
+    files.insert("shN_labels.webp".to_string(), labels);
+    files
+}
@@ -0,0 +1,114 @@
+//! Coordinate system conversion of every attribute.
+
+mod common;
+
+use common::sample_bytes;
+use sog_decoder::types::{SogData, Splat};
+use sog_decoder::{CoordinateSystem, DecodeOptions, Direction, decode_with, unpack};
+use std::sync::OnceLock;
+
+const SYSTEMS: [CoordinateSystem; 5] = [
+    CoordinateSystem::GLTF,
+    CoordinateSystem::PLAYCANVAS,
+    CoordinateSystem::Y_UP_LEFT_HANDED,
+    CoordinateSystem::Z_UP_RIGHT_HANDED,
+    CoordinateSystem::Z_UP_LEFT_HANDED,
+];
+
+fn sog_data() -> &'static SogData {
+    static SOG_DATA: OnceLock<SogData> = OnceLock::new();
+    SOG_DATA.get_or_init(|| unpack(&sample_bytes()).unwrap())
+}
+
+fn decode_in(coordinate_system: CoordinateSystem) -> Splat {
+    let options = DecodeOptions::new()
+        .with_covariance(true)
+        .with_coordinate_system(coordinate_system);
+    decode_with(sog_data(), &options).unwrap()
+}
+
+/// Rows of the matrix from the SOG frame, x right, y down and z forward, into `system`.
+fn matrix(system: CoordinateSystem) -> [[f32; 3]; 3] {
+    let vector = |direction| match direction {
+        Direction::Right => [1.0, 0.0, 0.0],
+        Direction::Left => [-1.0, 0.0, 0.0],
+        Direction::Down => [0.0, 1.0, 0.0],
+        Direction::Up => [0.0, -1.0, 0.0],
+        Direction::Forward => [0.0, 0.0, 1.0],
+        Direction::Back => [0.0, 0.0, -1.0],
+    };
+    [vector(system.x), vector(system.y), vector(system.z)]
+}
+
+fn apply(m: &[[f32; 3]; 3], v: &[f32]) -> [f32; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+/// View-dependent color of bands 1 to 3 in direction `d`, as evaluated by 3DGS renderers.
+fn sh_color(coeffs: &[f32], [x, y, z]: [f32; 3]) -> f32 {
+    let (xx, yy, zz) = (x * x, y * y, z * z);
+    let basis = [
+        -0.488_602_5 * y,
+        0.488_602_5 * z,
+        -0.488_602_5 * x,
+        1.092_548_4 * x * y,
+        -1.092_548_4 * y * z,
+        0.315_391_57 * (2.0 * zz - xx - yy),
+        -1.092_548_4 * x * z,
+        0.546_274_2 * (xx - yy),
+        -0.590_043_6 * y * (3.0 * xx - yy),
+        2.890_611_4 * x * y * z,
+        -0.457_045_8 * y * (4.0 * zz - xx - yy),
+        0.373_176_33 * z * (2.0 * zz - 3.0 * xx - 3.0 * yy),
+        -0.457_045_8 * x * (4.0 * zz - xx - yy),
+        1.445_305_7 * z * (xx - yy),
+        -0.590_043_6 * x * (xx - 3.0 * yy),
+    ];
+    coeffs.iter().zip(basis).map(|(c, b)| c * b).sum()
+}
+
+#[test]
+fn attributes_follow_the_axes() {
+    let reference = decode_in(CoordinateSystem::OPENCV);
+    let directions = [[0.6, 0.0, 0.8], [0.0, -1.0, 0.0], [0.48, 0.6, -0.64]];
+    for system in SYSTEMS {
+        let m = matrix(system);
+        let decoded = decode_in(system);
+        assert!(system.is_valid());
+
+        let positions = decoded.position.chunks(3).zip(reference.position.chunks(3));
+        for (position, expected) in positions {
+            assert_eq!(position, apply(&m, expected));
+        }
+
+        // covariances are M * C * M^T, which checks rotations and scales together
+        let covariances = decoded.covariance.as_ref().unwrap();
+        let expected = reference.covariance.as_ref().unwrap();
+        for (c, expected) in covariances.chunks(6).zip(expected.chunks(6)).step_by(101) {
+            let full = [
+                [expected[0], expected[1], expected[2]],
+                [expected[1], expected[3], expected[4]],
+                [expected[2], expected[4], expected[5]],
+            ];
+            let rows = m.map(|row| apply(&full, &row));
+            let e = |i: usize, j: usize| (0..3).map(|k| rows[i][k] * m[j][k]).sum::<f32>();
+            let expected = [e(0, 0), e(0, 1), e(0, 2), e(1, 1), e(1, 2), e(2, 2)];
+            let max = expected.iter().fold(0f32, |max, v| max.max(v.abs()));
+            for (actual, expected) in c.iter().zip(expected) {
+                assert!((actual - expected).abs() <= max * 1e-4, "{system:?}");
+            }
+        }
+
+        // view-dependent color is the same in the transformed view direction
+        let sh_n = decoded.sh_n.as_ref().unwrap().chunks(15);
+        let expected = reference.sh_n.as_ref().unwrap().chunks(15);
+        for (coeffs, expected) in sh_n.zip(expected).step_by(101) {
+            let max = expected.iter().fold(0f32, |max, v| max.max(v.abs()));
+            for d in directions {
+                let color = sh_color(coeffs, apply(&m, &d));
+                let expected = sh_color(expected, d);
+                assert!((color - expected).abs() <= max * 1e-4, "{system:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Decoding `sample_data/pizza.sog` with the different output options.
+
+mod common;
+
+use common::{assert_same_splat, sample_bytes};
+use sog_decoder::types::{SH_C0, SogData, SogDataV2, Splat};
+use sog_decoder::{
+    DecodeOptions, QuaternionOrder, SplatBuffers, decode, decode_into, decode_with, unpack,
+};
+use std::sync::OnceLock;
+
+fn sog_data() -> &'static SogData {
+    static SOG_DATA: OnceLock<SogData> = OnceLock::new();
+    SOG_DATA.get_or_init(|| unpack(&sample_bytes()).unwrap())
+}
+
+fn sog_data_v2() -> &'static SogDataV2 {
+    match sog_data() {
+        SogData::V2(sog_data) => sog_data,
+        SogData::V1(_) => panic!("expected v2 data"),
+    }
+}
+
+fn splat() -> &'static Splat {
+    static SPLAT: OnceLock<Splat> = OnceLock::new();
+    SPLAT.get_or_init(|| decode(sog_data()).unwrap())
+}
+
+#[test]
+fn skipped_attributes_are_empty() {
+    let options = DecodeOptions::new()
+        .with_position(false)
+        .with_sh_0(false)
+        .with_sh_n(false);
+    let decoded = decode_with(sog_data(), &options).unwrap();
+    let splat = splat();
+
+    assert_eq!(decoded.count, splat.count);
+    assert!(decoded.position.is_empty());
+    assert!(decoded.sh_0.is_empty());
+    assert!(decoded.sh_n.is_none());
+    assert_eq!(decoded.rotation, splat.rotation);
+    assert_eq!(decoded.scale, splat.scale);
+}
+
+#[test]
+fn max_sh_degree_drops_higher_bands() {
+    let splat = splat();
+    let sh_n = splat.sh_n.as_ref().unwrap();
+    assert_eq!(splat.sh_degree, 3);
+
+    let decoded = decode_with(
+        sog_data(),
+        &DecodeOptions::new().with_max_sh_degree(Some(1)),
+    )
+    .unwrap();
+    assert_eq!(decoded.sh_degree, 1);
+    let decoded_sh_n = decoded.sh_n.unwrap();
+    assert_eq!(decoded_sh_n.len(), splat.count * 3 * 3);
+    // coefficients are stored per channel, the first 3 of each channel are band 1
+    for (decoded, full) in decoded_sh_n.chunks(3).zip(sh_n.chunks(15)) {
+        assert_eq!(decoded, &full[..3]);
+    }
+
+    let decoded = decode_with(
+        sog_data(),
+        &DecodeOptions::new().with_max_sh_degree(Some(0)),
+    )
+    .unwrap();
+    assert_eq!(decoded.sh_degree, 0);
+    assert!(decoded.sh_n.is_none());
+    assert_eq!(decoded.sh_0, splat.sh_0);
+}
+
+#[test]
+fn activate_outputs_render_ready_values() {
+    let splat = splat();
+    let activated = decode_with(sog_data(), &DecodeOptions::new().with_activate(true)).unwrap();
+
+    let mut expected = splat.clone();
+    expected.activate();
+    assert_same_splat(&activated, &expected);
+
+    for (activated, raw) in activated.scale.iter().zip(&splat.scale) {
+        assert!((activated - raw.exp()).abs() <= 1e-6 * raw.exp());
+    }
+    for (activated, raw) in activated.sh_0.chunks(4).zip(splat.sh_0.chunks(4)) {
+        for c in 0..3 {
+            assert!((activated[c] - (SH_C0 * raw[c] + 0.5)).abs() < 1e-6);
+        }
+        let opacity = 1.0 / (1.0 + (-raw[3]).exp());
+        assert!((0.0..=1.0).contains(&activated[3]));
+        assert!((activated[3] - opacity).abs() < 1e-6);
+    }
+    assert_eq!(activated.position, splat.position);
+    assert_eq!(activated.rotation, splat.rotation);
+    assert_eq!(activated.sh_n, splat.sh_n);
+}
+
+#[test]
+fn decode_into_fills_the_given_slices() {
+    let splat = splat();
+    let count = splat.count;
+    let sh_n_len = splat.sh_n.as_ref().unwrap().len();
+
+    // oversized slices keep their tail
+    let mut position = vec![f32::NAN; count * 3 + 1];
+    let mut rotation = vec![0.0; count * 4];
+    let mut scale = vec![0.0; count * 3];
+    let mut sh_0 = vec![0.0; count * 4];
+    let mut sh_n = vec![0.0; sh_n_len];
+    let mut buffers = SplatBuffers {
+        position: Some(&mut position),
+        rotation: Some(&mut rotation),
+        scale: Some(&mut scale),
+        sh_0: Some(&mut sh_0),
+        sh_n: Some(&mut sh_n),
+    };
+    decode_into(sog_data_v2(), &mut buffers).unwrap();
+    assert_eq!(position[..count * 3], splat.position);
+    assert!(position[count * 3].is_nan());
+    assert_eq!(rotation, splat.rotation);
+    assert_eq!(scale, splat.scale);
+    assert_eq!(sh_0, splat.sh_0);
+    assert_eq!(&sh_n, splat.sh_n.as_ref().unwrap());
+
+    // only attributes with a slice are decoded
+    let mut scale = vec![0.0; count * 3];
+    let mut buffers = SplatBuffers {
+        scale: Some(&mut scale),
+        ..Default::default()
+    };
+    decode_into(sog_data_v2(), &mut buffers).unwrap();
+    assert_eq!(scale, splat.scale);
+}
+
+#[test]
+fn decode_into_rejects_short_slices() {
+    let mut sh_0 = vec![0.0; splat().count * 4 - 1];
+    let mut buffers = SplatBuffers {
+        sh_0: Some(&mut sh_0),
+        ..Default::default()
+    };
+    let e = decode_into(sog_data_v2(), &mut buffers).unwrap_err();
+    assert_eq!(e.code(), "invalid_size");
+    assert!(sh_0.iter().all(|v| *v == 0.0));
+}
+
+#[test]
+fn quaternion_order_and_normalisation() {
+    let splat = splat();
+    let options = DecodeOptions::new()
+        .with_quaternion_order(QuaternionOrder::Xyzw)
+        .with_normalize_rotations(true);
+    let decoded = decode_with(sog_data(), &options).unwrap();
+
+    for (xyzw, wxyz) in decoded.rotation.chunks(4).zip(splat.rotation.chunks(4)) {
+        let norm = wxyz.iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((xyzw.iter().map(|v| v * v).sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!((xyzw[3] - wxyz[0] / norm).abs() < 1e-6);
+        for c in 0..3 {
+            assert!((xyzw[c] - wxyz[c + 1] / norm).abs() < 1e-6);
+        }
+    }
+}
+
+/// Rotate `v` by the `w, x, y, z` quaternion `q` as `q * v * q^-1`.
+fn rotate(q: &[f32], v: [f32; 3]) -> [f32; 3] {
+    let norm = q.iter().map(|v| v * v).sum::<f32>();
+    let (w, u) = (q[0], [q[1], q[2], q[3]]);
+    let cross = |a: [f32; 3], b: [f32; 3]| {
+        [
+            a[1] * b[2] - a[2] * b[1],
+            a[2] * b[0] - a[0] * b[2],
+            a[0] * b[1] - a[1] * b[0],
+        ]
+    };
+    let t = cross(u, v).map(|c| 2.0 * c / norm);
+    let ut = cross(u, t);
+    std::array::from_fn(|i| v[i] + w * t[i] + ut[i])
+}
+
+#[test]
+fn rotation_matrices_and_covariances() {
+    let splat = splat();
+    let options = DecodeOptions::new()
+        .with_rotation(false)
+        .with_rotation_matrix(true)
+        .with_covariance(true)
+        .with_activate(true);
+    let decoded = decode_with(sog_data(), &options).unwrap();
+    assert!(decoded.rotation.is_empty());
+    let matrices = decoded.rotation_matrix.as_ref().unwrap();
+    let covariances = decoded.covariance.as_ref().unwrap();
+    assert_eq!(matrices.len(), splat.count * 9);
+    assert_eq!(covariances.len(), splat.count * 6);
+
+    let splats = splat
+        .rotation
+        .chunks(4)
+        .zip(splat.scale.chunks(3))
+        .zip(matrices.chunks(9).zip(covariances.chunks(6)));
+    for ((q, log_scale), (r, covariance)) in splats.step_by(97) {
+        // columns are the rotated axes
+        for axis in 0..3 {
+            let rotated = rotate(q, std::array::from_fn(|i| (i == axis) as u8 as f32));
+            for row in 0..3 {
+                assert!((r[row * 3 + axis] - rotated[row]).abs() < 1e-5);
+            }
+        }
+
+        // covariances come from log scales, also when scales are activated
+        let m = |i: usize, j: usize| r[i * 3 + j] * log_scale[j].exp();
+        let c = |i: usize, j: usize| (0..3).map(|k| m(i, k) * m(j, k)).sum::<f32>();
+        let expected = [c(0, 0), c(0, 1), c(0, 2), c(1, 1), c(1, 2), c(2, 2)];
+        let max = expected.iter().fold(0f32, |max, v| max.max(v.abs()));
+        for (actual, expected) in covariance.iter().zip(expected) {
+            assert!((actual - expected).abs() <= max * 1e-5);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Round trip of `sample_data/pizza.sog` through `encode`, `pack`, `unpack` and `decode`.
+
+use sog_decoder::types::Splat;
+use sog_decoder::{EncodeOptions, decode, encode, pack, unpack};
+use std::sync::OnceLock;
+
+/// Splats taken from the sample, enough for a full palette while keeping debug builds quick.
+const COUNT: usize = 4096;
+
+fn sample() -> Splat {
+    static SAMPLE: OnceLock<Splat> = OnceLock::new();
+    SAMPLE.get_or_init(read_sample).clone()
+}
+
+fn read_sample() -> Splat {
+    let file = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../sample_data/pizza.sog"
+    ))
+    .unwrap();
+    let splat = decode(&unpack(&file).unwrap()).unwrap();
+    let coeffs = splat.sh_n.as_ref().unwrap().len() / splat.count;
+    Splat {
+        count: COUNT,
+        position: splat.position[..COUNT * 3].to_vec(),
+        rotation: splat.rotation[..COUNT * 4].to_vec(),
+        scale: splat.scale[..COUNT * 3].to_vec(),
+        sh_0: splat.sh_0[..COUNT * 4].to_vec(),
+        sh_n: splat.sh_n.map(|sh_n| sh_n[..COUNT * coeffs].to_vec()),
+        ..splat
+    }
+}
+
+fn max_error(a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len());
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn round_trip() {
+    let splat = sample();
+    let options = EncodeOptions {
+        sh_palette_size: 256,
+        ..Default::default()
+    };
+    let file = pack(&encode(&splat, options).unwrap()).unwrap();
+    let decoded = decode(&unpack(&file).unwrap()).unwrap();
+
+    assert_eq!(decoded.count, COUNT);
+    assert_eq!(decoded.sh_degree, splat.sh_degree);
+
+    // 16 bits over a range of about 0.5
+    assert!(max_error(&splat.position, &decoded.position) < 1e-4);
+    // the sample is quantized already, so scales and colors fit the codebooks exactly
+    assert!(max_error(&splat.scale, &decoded.scale) < 1e-6);
+    assert!(max_error(&splat.sh_0, &decoded.sh_0) < 1e-4);
+
+    // q and -q are the same rotation
+    let dot = splat
+        .rotation
+        .chunks_exact(4)
+        .zip(decoded.rotation.chunks_exact(4))
+        .map(|(a, b)| a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>().abs())
+        .fold(1.0, f32::min);
+    assert!(dot > 0.999, "rotation dot {}", dot);
+
+    // shN is clustered into a smaller palette, so only the overall error is bounded
+    let (a, b) = (splat.sh_n.unwrap(), decoded.sh_n.unwrap());
+    let rmse = (a
+        .iter()
+        .zip(&b)
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum::<f32>()
+        / a.len() as f32)
+        .sqrt();
+    assert!(rmse < 0.05, "shN rmse {}", rmse);
+}
+
+#[test]
+fn rejects_non_finite_values() {
+    let mut splat = sample();
+    splat.position[3 * 100 + 1] = f32::NAN;
+    let error = encode(&splat, EncodeOptions::default()).unwrap_err();
+    assert_eq!(error.code(), "invalid_data");
+    assert!(error.to_string().contains("splat 100"), "{}", error);
+}
+
+#[test]
+fn rejects_counts_beyond_u32() {
+    let splat = Splat {
+        count: u32::MAX as usize + 1,
+        ..sample()
+    };
+    let error = encode(&splat, EncodeOptions::default()).unwrap_err();
+    assert_eq!(error.code(), "invalid_size");
+    assert!(error.to_string().contains("too many splats"), "{}", error);
+}
@@ -0,0 +1,65 @@
+//! Errors name the entry, image and splat involved and carry stable codes.
+
+mod common;
+
+use common::{archive, edit_image, file_name, meta_json, sample_files, set_meta_json};
+use sog_decoder::{decode, unpack};
+use std::error::Error as _;
+
+#[test]
+fn broken_images_name_their_entry() {
+    let mut files = sample_files();
+    let sh0 = file_name(&files, "sh0", 0);
+    let data = files.get_mut(&sh0).unwrap();
+    data.truncate(data.len() / 2);
+
+    let e = decode(&unpack(&archive(&files)).unwrap()).unwrap_err();
+    assert_eq!(e.code(), "decode_image");
+    assert_eq!(e.image(), Some("sh0"));
+    assert_eq!(e.entry(), Some(sh0.as_str()));
+    assert!(e.to_string().contains("sh0"), "{e}");
+    assert!(e.source().is_some());
+}
+
+#[test]
+fn splat_errors_name_the_splat() {
+    let mut files = sample_files();
+    let quats = file_name(&files, "quats", 0);
+    edit_image(&mut files, &quats, |pixels| pixels[42 * 4 + 3] = 7);
+
+    let e = decode(&unpack(&archive(&files)).unwrap()).unwrap_err();
+    assert_eq!(e.code(), "invalid_rotation_mode");
+    assert_eq!(e.splat_index(), Some(42));
+    assert_eq!(e.image(), Some("quats"));
+    assert_eq!(e.entry(), Some(quats.as_str()));
+    assert!(e.to_string().contains("splat 42"), "{e}");
+}
+
+#[test]
+fn meta_json_errors_keep_their_message() {
+    let mut files = sample_files();
+    let original = meta_json(&files);
+    let mut meta = original.clone();
+    meta["means"]["mins"] = serde_json::json!([0.0, 1.0]);
+    set_meta_json(&mut files, &meta);
+    let e = unpack(&archive(&files)).unwrap_err();
+    assert_eq!(e.code(), "invalid_vector");
+    assert!(e.to_string().contains("means.mins"), "{e}");
+
+    let mut meta = original.clone();
+    meta["scales"]["codebook"] = vec![0.0; 8].into();
+    set_meta_json(&mut files, &meta);
+    let e = unpack(&archive(&files)).unwrap_err();
+    assert_eq!(e.code(), "invalid_codebook");
+    assert!(e.to_string().contains("scales.codebook"), "{e}");
+
+    let mut meta = original.clone();
+    meta.as_object_mut().unwrap().remove("quats");
+    set_meta_json(&mut files, &meta);
+    let e = unpack(&archive(&files)).unwrap_err();
+    assert_eq!(e.code(), "deserialize_meta_json");
+    assert!(e.source().is_some());
+
+    let e = unpack(b"not a zip archive").unwrap_err();
+    assert_eq!(e.code(), "invalid_zip");
+}
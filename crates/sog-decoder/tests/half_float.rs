@@ -0,0 +1,53 @@
+//! Half float output.
+#![cfg(feature = "f16")]
+
+mod common;
+
+use common::sample_bytes;
+use sog_decoder::half::f16;
+use sog_decoder::{DecodeOptions, Positions, decode, decode_f16, unpack};
+
+#[test]
+fn decode_f16_matches_converted_f32() {
+    let sog_data = unpack(&sample_bytes()).unwrap();
+    let splat = decode(&sog_data).unwrap();
+    let options = DecodeOptions::new();
+
+    let decoded = decode_f16(&sog_data, &options, false).unwrap();
+    let expected = splat.to_f16(false);
+    let Positions::F32(position) = &decoded.position else {
+        panic!("expected f32 positions");
+    };
+    assert_eq!(position, &splat.position);
+    assert_eq!(decoded.rotation, expected.rotation);
+    assert_eq!(decoded.scale, expected.scale);
+    assert_eq!(decoded.sh_0, expected.sh_0);
+    assert_eq!(decoded.sh_n, expected.sh_n);
+    assert_eq!(decoded.sh_degree, splat.sh_degree);
+
+    for (half, full) in decoded.sh_0.iter().zip(&splat.sh_0) {
+        assert!((half.to_f32() - full).abs() <= full.abs() * f16::EPSILON.to_f32());
+    }
+
+    let decoded = decode_f16(&sog_data, &options, true).unwrap();
+    let Positions::F16(position) = &decoded.position else {
+        panic!("expected f16 positions");
+    };
+    let Positions::F16(expected) = splat.to_f16(true).position else {
+        panic!("expected f16 positions");
+    };
+    assert_eq!(position, &expected);
+}
+
+#[test]
+fn decode_f16_skips_disabled_attributes() {
+    let sog_data = unpack(&sample_bytes()).unwrap();
+    let options = DecodeOptions::new().with_position(false).with_sh_n(false);
+    let decoded = decode_f16(&sog_data, &options, true).unwrap();
+    let Positions::F32(position) = &decoded.position else {
+        panic!("expected empty f32 positions");
+    };
+    assert!(position.is_empty());
+    assert!(decoded.sh_n.is_none());
+    assert_eq!(decoded.scale.len(), decoded.count * 3);
+}
@@ -0,0 +1,84 @@
+//! Hostile input fails with errors instead of panicking.
+
+mod common;
+
+use common::{SAMPLE_DIR, archive, meta_json, sample_files, set_meta_json, v1_files};
+use sog_decoder::{DirSource, SogSource, decode, unpack, unpack_source};
+use std::collections::HashMap;
+use std::io;
+
+/// Unpack and decode, discarding the result; only panics matter.
+fn try_decode(file: &[u8]) {
+    if let Ok(sog_data) = unpack(file) {
+        let _ = decode(&sog_data);
+    }
+}
+
+/// `meta.json` from memory and the images from the sample directory.
+struct DirOverlay(HashMap<String, Vec<u8>>);
+
+impl SogSource for DirOverlay {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        match name {
+            "meta.json" => Ok(self.0[name].clone()),
+            _ => DirSource::new(SAMPLE_DIR).read(name),
+        }
+    }
+}
+
+#[test]
+fn dir_source_stays_inside_the_directory() {
+    let source = DirSource::new(SAMPLE_DIR);
+    assert!(source.read("meta.json").is_ok());
+    for name in ["../pizza.sog", "/etc/passwd", "a/../../pizza.sog"] {
+        let e = source.read(name).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{name}");
+    }
+
+    let mut files = sample_files();
+    let mut meta = meta_json(&files);
+    meta["quats"]["files"][0] = "../pizza/quats.webp".into();
+    set_meta_json(&mut files, &meta);
+    let e = unpack_source(&DirOverlay(files)).unwrap_err();
+    assert_eq!(e.code(), "read_file");
+}
+
+#[test]
+fn huge_counts_fail() {
+    let mut files = sample_files();
+    let mut meta = meta_json(&files);
+    meta["count"] = u32::MAX.into();
+    set_meta_json(&mut files, &meta);
+    let e = decode(&unpack(&archive(&files)).unwrap()).unwrap_err();
+    assert_eq!(e.code(), "image_size");
+
+    meta["shN"]["count"] = i32::MAX.into();
+    set_meta_json(&mut files, &meta);
+    assert!(decode(&unpack(&archive(&files)).unwrap()).is_err());
+}
+
+#[test]
+fn labels_past_the_palette_fail() {
+    let mut files = sample_files();
+    let mut meta = meta_json(&files);
+    meta["shN"]["count"] = 4096.into();
+    set_meta_json(&mut files, &meta);
+    let e = decode(&unpack(&archive(&files)).unwrap()).unwrap_err();
+    assert_eq!(e.code(), "shn_label_out_of_range");
+    assert!(e.splat_index().is_some());
+}
+
+#[test]
+fn truncated_and_corrupted_archives_do_not_panic() {
+    let file = archive(&v1_files());
+    for len in 0..file.len() {
+        try_decode(&file[..len]);
+    }
+    for i in 0..file.len() {
+        for flip in [0x01, 0x80, 0xff] {
+            let mut file = file.clone();
+            file[i] ^= flip;
+            try_decode(&file);
+        }
+    }
+}
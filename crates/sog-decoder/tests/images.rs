@@ -0,0 +1,68 @@
+//! WebP channel layouts and image sizes against the splat count.
+
+mod common;
+
+use common::{
+    archive, assert_same_splat, decode_image, file_name, meta_json, sample_bytes, sample_files,
+    set_meta_json,
+};
+use sog_decoder::{decode, unpack};
+
+#[test]
+fn images_without_alpha_decode_the_same() {
+    let expected = decode(&unpack(&sample_bytes()).unwrap()).unwrap();
+
+    // alpha holds data only in quats and sh0
+    let mut files = sample_files();
+    for (field, index) in [
+        ("means", 0),
+        ("means", 1),
+        ("scales", 0),
+        ("shN", 0),
+        ("shN", 1),
+    ] {
+        let name = file_name(&files, field, index);
+        let (width, height, pixels) = decode_image(&files[&name]);
+        let rgb: Vec<u8> = pixels
+            .chunks(4)
+            .flat_map(|rgba| &rgba[..3])
+            .copied()
+            .collect();
+        let mut webp = Vec::new();
+        image_webp::WebPEncoder::new(&mut webp)
+            .encode(&rgb, width, height, image_webp::ColorType::Rgb8)
+            .unwrap();
+        files.insert(name, webp);
+    }
+    let decoded = decode(&unpack(&archive(&files)).unwrap()).unwrap();
+    assert_same_splat(&decoded, &expected);
+}
+
+#[test]
+fn images_smaller_than_the_count_fail() {
+    let mut files = sample_files();
+    let (width, height, _) = decode_image(&files[&file_name(&files, "means", 0)]);
+    let mut meta = meta_json(&files);
+    meta["count"] = (width * height + 1).into();
+    set_meta_json(&mut files, &meta);
+
+    let e = decode(&unpack(&archive(&files)).unwrap()).unwrap_err();
+    assert_eq!(e.code(), "image_size");
+    assert!(e.image().is_some());
+    assert!(e.entry().unwrap().ends_with(".webp"));
+}
+
+#[test]
+fn centroids_smaller_than_the_palette_fail() {
+    let mut files = sample_files();
+    let (width, height, _) = decode_image(&files[&file_name(&files, "shN", 0)]);
+    let mut meta = meta_json(&files);
+    let coeff_count = 15;
+    meta["shN"]["count"] = (width * height / coeff_count + 64).into();
+    set_meta_json(&mut files, &meta);
+
+    let e = decode(&unpack(&archive(&files)).unwrap()).unwrap_err();
+    assert_eq!(e.code(), "image_size");
+    assert_eq!(e.image(), Some("shN_centroids"));
+    assert_eq!(e.entry(), Some(file_name(&files, "shN", 0).as_str()));
+}
@@ -0,0 +1,89 @@
+//! Header-only inspection of archives.
+
+mod common;
+
+use common::{
+    V1_COUNT, archive, decode_image, deflated_archive, meta_json, sample_bytes, sample_files,
+    v1_files,
+};
+use sog_decoder::{ImageInfo, SogInfo, inspect};
+use std::collections::HashMap;
+
+/// Entries of `info` match `files`, with the headers of every image.
+fn assert_entries(info: &SogInfo, files: &HashMap<String, Vec<u8>>) {
+    assert_eq!(info.entries.len(), files.len());
+    for entry in &info.entries {
+        let data = &files[&entry.name];
+        assert_eq!(entry.size, data.len() as u64, "{}", entry.name);
+        if entry.name.ends_with(".webp") {
+            let (width, height, _) = decode_image(data);
+            let image = entry.image.unwrap();
+            assert_eq!(
+                (image.width, image.height),
+                (width, height),
+                "{}",
+                entry.name
+            );
+        } else {
+            assert_eq!(entry.image, None);
+        }
+    }
+}
+
+#[test]
+fn inspects_the_sample() {
+    let files = sample_files();
+    let meta = meta_json(&files);
+    for file in [sample_bytes(), deflated_archive(&files)] {
+        let info = inspect(&file).unwrap();
+        assert_eq!(info.version, 2);
+        assert_eq!(info.count, 160554);
+        assert_eq!(info.sh_bands, 3);
+        assert_eq!(info.palette_count, Some(65536));
+        assert_eq!(info.antialias, meta["antialias"].as_bool().unwrap_or(false));
+        assert_entries(&info, &files);
+
+        let bounds = info.bounds.unwrap();
+        let min = meta["means"]["mins"][0].as_f64().unwrap() as f32;
+        assert_eq!(bounds.min.x, min.signum() * (min.abs().exp() - 1.0));
+        assert!(bounds.min.x <= bounds.max.x);
+    }
+}
+
+#[test]
+fn inspects_v1_archives() {
+    let files = v1_files();
+    let info = inspect(&archive(&files)).unwrap();
+    assert_eq!(info.version, 1);
+    assert_eq!(info.count, V1_COUNT);
+    assert_eq!(info.sh_bands, 1);
+    assert_eq!(info.palette_count, Some(2));
+    assert!(!info.antialias);
+    assert_entries(&info, &files);
+
+    let entry = info.entries.iter().find(|e| e.name == "sh0.webp").unwrap();
+    let expected = ImageInfo {
+        width: 2,
+        height: 2,
+        has_alpha: true,
+    };
+    assert_eq!(entry.image, Some(expected));
+}
+
+#[test]
+fn inspect_does_not_decode_images() {
+    let mut files = sample_files();
+    for (name, data) in files.iter_mut() {
+        if name.ends_with(".webp") {
+            // keep the headers, drop the image data
+            data.truncate(64);
+        }
+    }
+    let info = inspect(&archive(&files)).unwrap();
+    assert!(
+        info.entries
+            .iter()
+            .filter(|e| e.name.ends_with(".webp"))
+            .all(|e| e.image.is_some())
+    );
+}
@@ -0,0 +1,58 @@
+//! Interleaved output matches the attributes of [`decode`].
+
+mod common;
+
+use common::{sample_bytes, unpack_v2};
+use sog_decoder::types::SogData;
+use sog_decoder::{
+    Attribute, ComponentType, DecodeOptions, InterleavedLayout, decode_interleaved_into,
+    decode_with,
+};
+
+fn read_f32(record: &[u8], offset: usize, len: usize) -> Vec<f32> {
+    record[offset..][..len * 4]
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+        .collect()
+}
+
+#[test]
+fn records_hold_the_decoded_attributes() {
+    let sog_data = unpack_v2(&sample_bytes());
+    let mut layout = InterleavedLayout::packed(&[
+        (Attribute::Position, ComponentType::F32),
+        (Attribute::Rotation, ComponentType::F32),
+        (Attribute::Covariance, ComponentType::F32),
+        (Attribute::Sh0, ComponentType::F32),
+        (Attribute::ShN { bands: 1 }, ComponentType::F32),
+    ]);
+    // pad records to 16 bytes
+    let size = layout.stride;
+    layout.stride = size.next_multiple_of(16);
+    assert!(layout.stride > size);
+
+    let options = DecodeOptions::new().with_activate(true);
+    let count = sog_data.count as usize;
+    let mut out = vec![0xaa; layout.buffer_size(count).unwrap()];
+    decode_interleaved_into(&sog_data, &layout, &options, &mut out).unwrap();
+
+    let options = options.with_covariance(true).with_max_sh_degree(Some(1));
+    let splat = decode_with(&SogData::V2(sog_data), &options).unwrap();
+    let sh_n = splat.sh_n.as_ref().unwrap();
+    let covariance = splat.covariance.as_ref().unwrap();
+    let offsets: Vec<_> = layout.elements.iter().map(|e| e.offset).collect();
+    for (i, record) in out.chunks_exact(layout.stride).enumerate() {
+        assert_eq!(
+            read_f32(record, offsets[0], 3),
+            splat.position[i * 3..][..3]
+        );
+        assert_eq!(
+            read_f32(record, offsets[1], 4),
+            splat.rotation[i * 4..][..4]
+        );
+        assert_eq!(read_f32(record, offsets[2], 6), covariance[i * 6..][..6]);
+        assert_eq!(read_f32(record, offsets[3], 4), splat.sh_0[i * 4..][..4]);
+        assert_eq!(read_f32(record, offsets[4], 9), sh_n[i * 9..][..9]);
+        assert!(record[size..].iter().all(|b| *b == 0xaa));
+    }
+}
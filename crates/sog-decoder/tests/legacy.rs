@@ -0,0 +1,114 @@
+//! Legacy PlayCanvas SOGS v1 archives.
+
+mod common;
+
+use common::{V1_COUNT, archive, meta_json, sample_bytes, set_meta_json, v1_files};
+use sog_decoder::types::SogData;
+use sog_decoder::{decode, from_files, unpack, validate};
+
+fn close(a: &[f32], b: &[f32]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+}
+
+fn lerp(a: f32, b: f32, t: u8) -> f32 {
+    a + t as f32 / 255.0 * (b - a)
+}
+
+fn unlog(x: f32) -> f32 {
+    x.signum() * (x.abs().exp() - 1.0)
+}
+
+#[test]
+fn decodes_v1() {
+    let file = archive(&v1_files());
+    let sog_data = unpack(&file).unwrap();
+    let SogData::V1(v1) = &sog_data else {
+        panic!("expected v1 data");
+    };
+    assert_eq!(v1.count as usize, V1_COUNT);
+    let splat = decode(&sog_data).unwrap();
+    assert_eq!(splat.count, V1_COUNT);
+
+    let position = |t: f32| {
+        let (mins, maxs) = ([-1.0, 0.0, 0.5], [1.0, 2.0, 0.5]);
+        [0, 1, 2].map(|c| unlog(mins[c] + t * (maxs[c] - mins[c])))
+    };
+    let expected = [0.0, 1.0, 255.0 / 65535.0, 256.0 / 65535.0].map(position);
+    assert!(
+        close(&splat.position, expected.as_flattened()),
+        "{:?}",
+        splat.position
+    );
+
+    let scale = |pixel: [u8; 3]| {
+        let (mins, maxs) = ([-4.0, -3.0, -2.0], [1.0, 2.0, 3.0]);
+        [0, 1, 2].map(|c| lerp(mins[c], maxs[c], pixel[c]))
+    };
+    let expected = [[0, 0, 0], [255, 255, 255], [51, 102, 153], [0, 0, 0]].map(scale);
+    assert!(
+        close(&splat.scale, expected.as_flattened()),
+        "{:?}",
+        splat.scale
+    );
+
+    // opacity is stored as a logit like the colors
+    let color = |pixel: [u8; 4]| {
+        let (mins, maxs) = ([-1.0, -1.0, -1.0, -5.0], [1.0, 1.0, 1.0, 5.0]);
+        [0, 1, 2, 3].map(|c| lerp(mins[c], maxs[c], pixel[c]))
+    };
+    let pixels = [[0; 4], [255; 4], [51, 102, 153, 204], [0, 255, 0, 255]];
+    assert!(
+        close(&splat.sh_0, pixels.map(color).as_flattened()),
+        "{:?}",
+        splat.sh_0
+    );
+
+    // same packed quaternions as the sample
+    let sample = decode(&unpack(&sample_bytes()).unwrap()).unwrap();
+    assert!(close(&splat.rotation, &sample.rotation[..V1_COUNT * 4]));
+
+    // palette entry e holds byte 10 * (4 * (3 * e + c) + channel) for coefficient c
+    assert_eq!(splat.sh_degree, 1);
+    let entry = |e: usize| -> Vec<f32> {
+        (0..3)
+            .flat_map(|channel| {
+                (0..3).map(move |c| lerp(-0.5, 0.5, (10 * (4 * (3 * e + c) + channel)) as u8))
+            })
+            .collect()
+    };
+    let expected = [entry(0), entry(1), entry(1), entry(0)].concat();
+    assert!(
+        close(splat.sh_n.as_ref().unwrap(), &expected),
+        "{:?}",
+        splat.sh_n
+    );
+
+    assert!(validate(&file).is_valid(), "{:?}", validate(&file).findings);
+    let files = v1_files();
+    assert_eq!(
+        decode(&from_files(files).unwrap()).unwrap().sh_0,
+        splat.sh_0
+    );
+}
+
+#[test]
+fn detects_version() {
+    let mut files = v1_files();
+    let mut meta = meta_json(&files);
+    meta["version"] = 1.into();
+    set_meta_json(&mut files, &meta);
+    assert!(matches!(unpack(&archive(&files)).unwrap(), SogData::V1(_)));
+
+    meta["version"] = 3.into();
+    set_meta_json(&mut files, &meta);
+    let error = unpack(&archive(&files)).unwrap_err().to_string();
+    assert!(error.contains("unsupported version: 3"), "{error}");
+
+    // neither a version nor v1 attribute shapes
+    let mut meta = meta_json(&files);
+    meta.as_object_mut().unwrap().remove("version");
+    meta["means"].as_object_mut().unwrap().remove("shape");
+    set_meta_json(&mut files, &meta);
+    let error = unpack(&archive(&files)).unwrap_err().to_string();
+    assert!(error.contains("unknown meta.json version"), "{error}");
+}
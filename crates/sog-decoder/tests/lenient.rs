@@ -0,0 +1,81 @@
+//! Lenient unpacking and decoding of partially broken copies of the sample.
+
+mod common;
+
+use common::{archive, edit_image, file_name, meta_json, sample_files, set_meta_json};
+use sog_decoder::{DecodeOptions, Severity, decode_lenient, unpack, unpack_lenient};
+use std::collections::HashMap;
+
+fn assert_sh_n_dropped(files: &HashMap<String, Vec<u8>>) {
+    let file = archive(files);
+    assert!(unpack(&file).is_err());
+
+    let (sog_data, warnings) = unpack_lenient(&file).unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].severity, Severity::Warning);
+    assert!(
+        warnings[0].message.starts_with("shN dropped"),
+        "{}",
+        warnings[0]
+    );
+
+    let (splat, warnings) = decode_lenient(&sog_data, &DecodeOptions::new()).unwrap();
+    assert!(warnings.is_empty());
+    assert_eq!(splat.sh_degree, 0);
+    assert!(splat.sh_n.is_none());
+}
+
+#[test]
+fn unpack_drops_missing_sh_n_entries() {
+    for index in [0, 1] {
+        let mut files = sample_files();
+        files.remove(&file_name(&files, "shN", index));
+        assert_sh_n_dropped(&files);
+    }
+}
+
+#[test]
+fn unpack_drops_malformed_sh_n() {
+    let mut files = sample_files();
+    let mut meta = meta_json(&files);
+    meta["shN"]["bands"] = "three".into();
+    set_meta_json(&mut files, &meta);
+    assert_sh_n_dropped(&files);
+
+    let mut files = sample_files();
+    let mut meta = meta_json(&files);
+    meta["shN"]["codebook"] = serde_json::json!([0.0]);
+    set_meta_json(&mut files, &meta);
+    assert_sh_n_dropped(&files);
+}
+
+#[test]
+fn unpack_still_fails_without_other_images() {
+    let mut files = sample_files();
+    files.remove(&file_name(&files, "quats", 0));
+    assert!(unpack_lenient(&archive(&files)).is_err());
+}
+
+#[test]
+fn decode_recovers_broken_image_contents() {
+    let mut files = sample_files();
+    let labels = file_name(&files, "shN", 1);
+    edit_image(&mut files, &labels, |pixels| pixels[..8].fill(255));
+    // labels address 65536 entries, so shrink the palette for them to leave it
+    let mut meta = meta_json(&files);
+    meta["shN"]["count"] = 4096.into();
+    set_meta_json(&mut files, &meta);
+    let quats = file_name(&files, "quats", 0);
+    edit_image(&mut files, &quats, |pixels| pixels[4 * 3 + 3] = 0);
+
+    let (sog_data, warnings) = unpack_lenient(&archive(&files)).unwrap();
+    assert!(warnings.is_empty());
+    let (splat, warnings) = decode_lenient(&sog_data, &DecodeOptions::new()).unwrap();
+
+    assert!(splat.sh_n.is_none());
+    assert_eq!(&splat.rotation[3 * 4..4 * 4], &[1.0, 0.0, 0.0, 0.0]);
+    let codes: Vec<_> = warnings.iter().map(|w| w.code).collect();
+    assert_eq!(codes, ["invalid_rotation_mode", "shn_label_out_of_range"]);
+    assert_eq!(warnings[0].location.splat_index, Some(3));
+    assert_eq!(warnings[1].location.splat_index, Some(0));
+}
@@ -0,0 +1,112 @@
+//! Resource limits on the sample archive.
+
+mod common;
+
+use common::{SAMPLE_DIR, sample_bytes, sample_files};
+use sog_decoder::error::{Error, ParseError};
+use sog_decoder::{
+    Limit, LimitExceeded, Limits, from_files_with_limits, inspect_with_limits,
+    unpack_dir_with_limits, unpack_reader_with_limits, unpack_ref_with_limits,
+    unpack_source_with_limits, unpack_with_limits, validate_with_limits,
+};
+use std::io::Cursor;
+
+fn exceeded<T>(result: sog_decoder::error::Result<T>) -> LimitExceeded {
+    match result {
+        Err(Error::SogParse(ParseError::LimitExceeded(e))) => e,
+        Err(e) => panic!("expected an exceeded limit, got {e}"),
+        Ok(_) => panic!("expected an exceeded limit"),
+    }
+}
+
+#[test]
+fn default_has_no_limits() {
+    assert_eq!(Limits::default(), Limits::NONE);
+    unpack_with_limits(&sample_bytes(), &Limits::default()).unwrap();
+}
+
+#[test]
+fn enforces_every_limit() {
+    let file = sample_bytes();
+
+    let e = exceeded(unpack_with_limits(&file, &Limits::NONE.with_max_entries(3)));
+    assert_eq!((e.limit, e.max), (Limit::Entries, 3));
+
+    let e = exceeded(unpack_with_limits(
+        &file,
+        &Limits::NONE.with_max_splats(1000),
+    ));
+    assert_eq!((e.limit, e.actual), (Limit::Splats, 160554));
+    assert_eq!(e.entry.as_deref(), Some("meta.json"));
+
+    let limits = Limits::NONE.with_max_decompressed_bytes(100_000);
+    let e = exceeded(unpack_reader_with_limits(Cursor::new(&file), &limits));
+    assert_eq!(e.limit, Limit::DecompressedBytes);
+    assert!(e.actual > 100_000);
+
+    let limits = Limits::NONE.with_max_image_pixels(1 << 16);
+    let e = exceeded(unpack_ref_with_limits(&file, &limits));
+    assert_eq!(e.limit, Limit::ImagePixels);
+    assert!(e.entry.unwrap().ends_with(".webp"));
+}
+
+#[test]
+fn validate_and_inspect_enforce_limits() {
+    let file = sample_bytes();
+    let limits = Limits::NONE.with_max_splats(1000);
+    let report = validate_with_limits(&file, &limits);
+    assert!(report.errors().any(|f| f.code == "limit_exceeded"));
+
+    let e = exceeded(inspect_with_limits(
+        &file,
+        &Limits::NONE.with_max_entries(3),
+    ));
+    assert_eq!(e.limit, Limit::Entries);
+}
+
+#[test]
+fn from_files_enforces_limits() {
+    from_files_with_limits(sample_files(), &Limits::NONE).unwrap();
+
+    let e = exceeded(from_files_with_limits(
+        sample_files(),
+        &Limits::NONE.with_max_entries(3),
+    ));
+    assert_eq!((e.limit, e.actual), (Limit::Entries, 8));
+
+    let e = exceeded(from_files_with_limits(
+        sample_files(),
+        &Limits::NONE.with_max_splats(1000),
+    ));
+    assert_eq!(e.limit, Limit::Splats);
+
+    let limits = Limits::NONE.with_max_image_pixels(1 << 16);
+    let e = exceeded(from_files_with_limits(sample_files(), &limits));
+    assert_eq!(e.limit, Limit::ImagePixels);
+}
+
+#[test]
+fn dir_and_source_enforce_limits() {
+    unpack_dir_with_limits(SAMPLE_DIR, &Limits::NONE).unwrap();
+
+    let limits = Limits::NONE.with_max_decompressed_bytes(100_000);
+    let e = exceeded(unpack_dir_with_limits(SAMPLE_DIR, &limits));
+    assert_eq!(e.limit, Limit::DecompressedBytes);
+    assert!(e.actual > 100_000);
+
+    let e = exceeded(unpack_dir_with_limits(
+        SAMPLE_DIR,
+        &Limits::NONE.with_max_splats(1000),
+    ));
+    assert_eq!(e.limit, Limit::Splats);
+
+    let files = sample_files();
+    let e = exceeded(unpack_source_with_limits(
+        &files,
+        &Limits::NONE.with_max_image_pixels(1 << 16),
+    ));
+    assert_eq!(e.limit, Limit::ImagePixels);
+
+    let e = exceeded(unpack_source_with_limits(&files, &limits));
+    assert_eq!(e.limit, Limit::DecompressedBytes);
+}
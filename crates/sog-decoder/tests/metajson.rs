@@ -0,0 +1,36 @@
+//! Reading, editing and writing `meta.json` with [`SogMeta`].
+
+mod common;
+
+use common::{meta_json, sample_files};
+use serde_json::json;
+use sog_decoder::SogMeta;
+
+#[test]
+fn round_trip_keeps_unknown_fields() {
+    let mut value = meta_json(&sample_files());
+    value["generator"] = json!({ "name": "tool", "version": "1.2" });
+    value["means"]["precision"] = json!(16);
+    value["shN"]["note"] = json!("kept");
+
+    let mut meta: SogMeta = serde_json::from_value(value.clone()).unwrap();
+    assert_eq!(meta.count, 160554);
+    assert_eq!(meta.extra["generator"]["name"], "tool");
+    assert_eq!(meta.means.extra["precision"], 16);
+    assert_eq!(meta.sh_n.as_ref().unwrap().extra["note"], "kept");
+
+    // bounds are read as f32, so only a written document round-trips exactly
+    let written = serde_json::to_value(&meta).unwrap();
+    let reread: SogMeta = serde_json::from_value(written.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&reread).unwrap(), written);
+    let keys = |v: &serde_json::Value| v.as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+    assert_eq!(keys(&written), keys(&value));
+    assert_eq!(keys(&written["means"]), keys(&value["means"]));
+
+    meta.antialias = Some(true);
+    let written = serde_json::to_value(&meta).unwrap();
+    assert_eq!(written["antialias"], true);
+    assert_eq!(written["generator"], value["generator"]);
+    assert_eq!(written["means"]["precision"], 16);
+    assert_eq!(written["shN"]["note"], "kept");
+}
@@ -0,0 +1,54 @@
+//! Lossless repacking of unpacked SOG data.
+
+mod common;
+
+use common::{
+    archive, entries, file_name, meta_json, sample_bytes, sample_files, set_meta_json, unpack_v2,
+};
+use sog_decoder::{EntryNames, PackOptions, decode, pack, unpack};
+
+#[test]
+fn repack_keeps_images_and_decodes_the_same() {
+    let file = sample_bytes();
+    let repacked = pack(&unpack_v2(&file)).unwrap();
+
+    let (original, packed) = (sample_files(), entries(&repacked));
+    for field in ["means", "scales", "quats", "sh0", "shN"] {
+        let files = meta_json(&original)[field]["files"]
+            .as_array()
+            .unwrap()
+            .clone();
+        for name in files.iter().map(|name| name.as_str().unwrap()) {
+            assert_eq!(original[name], packed[name], "{name}");
+        }
+    }
+    assert_eq!(
+        decode(&unpack(&file).unwrap()).unwrap().position,
+        decode(&unpack(&repacked).unwrap()).unwrap().position
+    );
+    assert_eq!(pack(&unpack_v2(&repacked)).unwrap(), repacked);
+}
+
+#[test]
+fn repack_keeps_entry_names() {
+    let mut files = sample_files();
+    let mut meta = meta_json(&files);
+    let sh0 = file_name(&files, "sh0", 0);
+    let data = files.remove(&sh0).unwrap();
+    files.insert("colors/base.webp".to_string(), data);
+    meta["sh0"]["files"][0] = "colors/base.webp".into();
+    set_meta_json(&mut files, &meta);
+
+    let sog_data = unpack_v2(&archive(&files));
+    let repacked = entries(&pack(&sog_data).unwrap());
+    assert!(repacked.contains_key("colors/base.webp"));
+    assert_eq!(meta_json(&repacked)["sh0"]["files"][0], "colors/base.webp");
+
+    let options = PackOptions {
+        names: Some(EntryNames::default()),
+        ..Default::default()
+    };
+    let renamed = entries(&sog_data.to_sog_bytes_with(&options).unwrap());
+    assert!(renamed.contains_key("sh0.webp"));
+    assert!(!renamed.contains_key("colors/base.webp"));
+}
@@ -0,0 +1,53 @@
+//! Decoding gives the same output and errors whether it runs on one thread or many.
+
+mod common;
+
+use common::{archive, edit_image, file_name, sample_files};
+use sog_decoder::{decode, unpack};
+
+#[test]
+fn errors_report_the_lowest_failing_splat() {
+    let mut files = sample_files();
+    let quats = file_name(&files, "quats", 0);
+    edit_image(&mut files, &quats, |pixels| {
+        for splat in [150_000, 70_000, 5_000] {
+            pixels[splat * 4 + 3] = 0;
+        }
+    });
+    let sog_data = unpack(&archive(&files)).unwrap();
+
+    let decode_error = || {
+        let e = decode(&sog_data).unwrap_err();
+        assert_eq!(e.code(), "invalid_rotation_mode");
+        e.splat_index()
+    };
+    assert_eq!(decode_error(), Some(5_000));
+
+    #[cfg(feature = "rayon")]
+    for threads in [1, 2, 8] {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        assert_eq!(pool.install(decode_error), Some(5_000));
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn thread_count_does_not_change_the_output() {
+    use common::{assert_same_splat, sample_bytes};
+
+    let sog_data = unpack(&sample_bytes()).unwrap();
+    let decode_on = |threads| {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        pool.install(|| decode(&sog_data).unwrap())
+    };
+    let expected = decode_on(1);
+    for threads in [2, 3, 8] {
+        assert_same_splat(&decode_on(threads), &expected);
+    }
+}
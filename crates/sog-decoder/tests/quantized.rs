@@ -0,0 +1,70 @@
+//! Quantized passthrough of the image pixels for GPU-side dequantization.
+
+mod common;
+
+use common::{
+    archive, decode_image, file_name, meta_json, sample_bytes, sample_files, set_meta_json,
+    unpack_v2,
+};
+use sog_decoder::types::SogData;
+use sog_decoder::{decode, decode_quantized};
+
+#[test]
+fn quantized_data_holds_the_image_pixels() {
+    let files = sample_files();
+    let sog_data = unpack_v2(&sample_bytes());
+    let quantized = decode_quantized(&sog_data).unwrap();
+
+    let images = [
+        (&quantized.means.means_l, file_name(&files, "means", 0)),
+        (&quantized.means.means_u, file_name(&files, "means", 1)),
+        (&quantized.scales.scales, file_name(&files, "scales", 0)),
+        (&quantized.quats.0, file_name(&files, "quats", 0)),
+        (&quantized.sh_0.sh_0, file_name(&files, "sh0", 0)),
+    ];
+    for (image, name) in images {
+        let (width, height, pixels) = decode_image(&files[&name]);
+        assert_eq!((image.width, image.height), (width, height), "{name}");
+        assert_eq!(image.pixels, pixels, "{name}");
+    }
+    let sh_n = quantized.sh_n.as_ref().unwrap();
+    assert_eq!(
+        sh_n.labels.pixels,
+        decode_image(&files[&file_name(&files, "shN", 1)]).2
+    );
+    assert_eq!(quantized.count, sog_data.count);
+    assert_eq!(quantized.scales.codebook.0, sog_data.scales.codebook.0);
+}
+
+#[test]
+fn codebook_lookups_match_decode() {
+    let sog_data = unpack_v2(&sample_bytes());
+    let quantized = decode_quantized(&sog_data).unwrap();
+    let splat = decode(&SogData::V2(sog_data)).unwrap();
+
+    let scales = &quantized.scales;
+    for (scale, pixel) in splat.scale.chunks(3).zip(scales.scales.pixels.chunks(4)) {
+        for (value, index) in scale.iter().zip(pixel) {
+            assert_eq!(*value, scales.codebook.0[*index as usize]);
+        }
+    }
+    let sh_0 = &quantized.sh_0;
+    for (color, pixel) in splat.sh_0.chunks(4).zip(sh_0.sh_0.pixels.chunks(4)) {
+        // opacity is a logit of the alpha, not a codebook entry
+        for (value, index) in color[..3].iter().zip(pixel) {
+            assert_eq!(*value, sh_0.codebook.0[*index as usize]);
+        }
+    }
+}
+
+#[test]
+fn quantized_images_are_checked_against_the_count() {
+    let mut files = sample_files();
+    let (width, height, _) = decode_image(&files[&file_name(&files, "quats", 0)]);
+    let mut meta = meta_json(&files);
+    meta["count"] = (width * height + 1).into();
+    set_meta_json(&mut files, &meta);
+
+    let e = decode_quantized(&unpack_v2(&archive(&files))).unwrap_err();
+    assert_eq!(e.code(), "image_size");
+}
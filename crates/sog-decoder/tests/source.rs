@@ -0,0 +1,231 @@
+//! Loading SOG data from readers, files, directories and custom sources.
+
+mod common;
+
+use common::{
+    SAMPLE, SAMPLE_DIR, archive, assert_same_splat, deflated_archive, meta_json, sample_bytes,
+    sample_files, set_meta_json,
+};
+use sog_decoder::types::SogData;
+use sog_decoder::{
+    SogSource, ZipSource, decode, decode_v2, from_files, unpack, unpack_dir, unpack_file,
+    unpack_reader, unpack_ref, unpack_source,
+};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+/// Storage keyed by a hash of the file name, recording the names read.
+struct HashedStore {
+    blobs: HashMap<u64, Vec<u8>>,
+    reads: RefCell<Vec<String>>,
+}
+
+impl HashedStore {
+    fn new(files: &HashMap<String, Vec<u8>>) -> Self {
+        Self {
+            blobs: files
+                .iter()
+                .map(|(name, data)| (Self::key(name), data.clone()))
+                .collect(),
+            reads: RefCell::default(),
+        }
+    }
+
+    fn key(name: &str) -> u64 {
+        name.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100_0000_01b3)
+        })
+    }
+}
+
+impl SogSource for HashedStore {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        self.reads.borrow_mut().push(name.to_string());
+        match self.blobs.get(&Self::key(name)) {
+            Some(data) => Ok(data.clone()),
+            None if name.ends_with(".webp") => Err(io::Error::other("store is offline")),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+/// Reader counting the bytes read through it.
+struct CountingReader<R> {
+    inner: R,
+    read: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Seek> Seek for CountingReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[test]
+fn readers_and_files_decode_like_bytes() {
+    let file = sample_bytes();
+    let expected = decode(&unpack(&file).unwrap()).unwrap();
+    let reader = unpack_reader(Cursor::new(&file)).unwrap();
+    assert_same_splat(&decode(&reader).unwrap(), &expected);
+    assert_same_splat(&decode(&unpack_file(SAMPLE).unwrap()).unwrap(), &expected);
+
+    let missing = concat!(env!("CARGO_MANIFEST_DIR"), "/../sample_data/missing.sog");
+    assert_eq!(unpack_file(missing).unwrap_err().code(), "io");
+}
+
+#[test]
+fn reader_reads_only_referenced_entries() {
+    const PADDING: usize = 16 << 20;
+    let mut files = sample_files();
+    files.insert("padding.bin".to_string(), vec![0; PADDING]);
+    let file = archive(&files);
+
+    let mut reader = CountingReader {
+        inner: Cursor::new(&file),
+        read: 0,
+    };
+    unpack_reader(&mut reader).unwrap();
+    // headers are read more than once, but the padding is skipped
+    assert!(
+        reader.read < PADDING as u64 / 2,
+        "read {} bytes",
+        reader.read
+    );
+}
+
+#[test]
+fn unbundled_files_decode_like_the_archive() {
+    let expected = decode(&unpack(&sample_bytes()).unwrap()).unwrap();
+    assert_same_splat(
+        &decode(&unpack_dir(SAMPLE_DIR).unwrap()).unwrap(),
+        &expected,
+    );
+    assert_same_splat(
+        &decode(&from_files(sample_files()).unwrap()).unwrap(),
+        &expected,
+    );
+}
+
+#[test]
+fn unbundled_files_report_missing_files() {
+    let mut files = sample_files();
+    files.remove("meta.json");
+    assert_eq!(from_files(files).unwrap_err().code(), "meta_json_not_found");
+
+    let mut files = sample_files();
+    let quats = meta_json(&files)["quats"]["files"][0]
+        .as_str()
+        .unwrap()
+        .to_string();
+    files.remove(&quats);
+    let e = from_files(files).unwrap_err();
+    assert_eq!(e.code(), "image_not_found");
+    assert_eq!(e.entry(), Some(quats.as_str()));
+
+    let missing = concat!(env!("CARGO_MANIFEST_DIR"), "/../sample_data/missing");
+    assert_eq!(
+        unpack_dir(missing).unwrap_err().code(),
+        "meta_json_not_found"
+    );
+}
+
+#[test]
+fn from_files_reads_shared_files() {
+    let mut files = sample_files();
+    let mut meta = meta_json(&files);
+    let quats = meta["quats"]["files"][0].as_str().unwrap().to_string();
+    let scales = meta["scales"]["files"][0].as_str().unwrap().to_string();
+    meta["scales"]["files"][0] = quats.clone().into();
+    set_meta_json(&mut files, &meta);
+    files.remove(&scales);
+    let expected = files[&quats].clone();
+
+    let SogData::V2(sog_data) = from_files(files).unwrap() else {
+        panic!("expected v2 data");
+    };
+    assert_eq!(sog_data.scales.scales, expected);
+    assert_eq!(sog_data.quats.0, expected);
+    decode(&SogData::V2(sog_data)).unwrap();
+}
+
+#[test]
+fn custom_sources_resolve_referenced_files() {
+    let expected = decode(&unpack(&sample_bytes()).unwrap()).unwrap();
+
+    let mut files = sample_files();
+    files.insert("unreferenced.bin".to_string(), vec![0; 16]);
+    let store = HashedStore::new(&files);
+    assert_same_splat(&decode(&unpack_source(&store).unwrap()).unwrap(), &expected);
+    let mut reads = store.reads.take();
+    assert_eq!(reads.remove(0), "meta.json");
+    reads.sort();
+    let mut referenced: Vec<_> = files
+        .keys()
+        .filter(|name| name.ends_with(".webp"))
+        .cloned()
+        .collect();
+    referenced.sort();
+    assert_eq!(reads, referenced);
+
+    let zip = ZipSource::new(Cursor::new(sample_bytes())).unwrap();
+    assert_same_splat(&decode(&unpack_source(&zip).unwrap()).unwrap(), &expected);
+    assert_same_splat(&decode(&unpack_source(&files).unwrap()).unwrap(), &expected);
+}
+
+#[test]
+fn custom_source_errors_name_the_file() {
+    let mut files = sample_files();
+    let quats = meta_json(&files)["quats"]["files"][0]
+        .as_str()
+        .unwrap()
+        .to_string();
+    files.remove(&quats);
+    let store = HashedStore::new(&files);
+    let e = unpack_source(&store).unwrap_err();
+    assert_eq!(e.code(), "read_file");
+    assert_eq!(e.entry(), Some(quats.as_str()));
+
+    let store = HashedStore::new(&HashMap::new());
+    assert_eq!(
+        unpack_source(&store).unwrap_err().code(),
+        "meta_json_not_found"
+    );
+}
+
+#[test]
+fn unpack_ref_borrows_stored_images() {
+    let file = sample_bytes();
+    let expected = decode(&unpack(&file).unwrap()).unwrap();
+    let range = file.as_ptr_range();
+
+    let sog_data = unpack_ref(&file).unwrap();
+    let images = [
+        &sog_data.means.means_l,
+        &sog_data.means.means_u,
+        &sog_data.scales.scales,
+        &sog_data.quats.0,
+        &sog_data.sh_0.sh_0,
+    ];
+    for image in images {
+        assert!(matches!(image, Cow::Borrowed(_)));
+        assert!(range.contains(&image.as_ptr()));
+    }
+    assert_same_splat(&decode_v2(&sog_data).unwrap(), &expected);
+    assert_same_splat(&decode_v2(&sog_data.into_owned()).unwrap(), &expected);
+
+    // deflated images are decompressed into owned buffers
+    let file = deflated_archive(&sample_files());
+    let sog_data = unpack_ref(&file).unwrap();
+    assert!(matches!(sog_data.quats.0, Cow::Owned(_)));
+    assert_same_splat(&decode_v2(&sog_data).unwrap(), &expected);
+}
@@ -0,0 +1,75 @@
+//! Diagnostics of `validate` on the sample and on broken copies of it.
+
+mod common;
+
+use common::{
+    archive, edit_image, file_name, meta_json, sample_bytes, sample_files, set_meta_json,
+};
+use sog_decoder::{Severity, validate};
+
+#[test]
+fn sample_is_valid() {
+    let report = validate(&sample_bytes());
+    assert!(report.is_valid(), "{:?}", report.findings);
+    assert_eq!(report.errors().count(), 0);
+}
+
+#[test]
+fn summarises_per_splat_errors() {
+    let mut files = sample_files();
+    let quats = file_name(&files, "quats", 0);
+    let count = meta_json(&files)["count"].as_u64().unwrap() as usize;
+    edit_image(&mut files, &quats, |pixels| {
+        pixels.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 0);
+    });
+
+    let report = validate(&archive(&files));
+    let errors: Vec<_> = report.errors().collect();
+    assert_eq!(errors.len(), 17);
+    for (splat_index, error) in errors[..16].iter().enumerate() {
+        assert_eq!(error.code, "invalid_rotation_mode");
+        assert_eq!(error.location.entry.as_deref(), Some(quats.as_str()));
+        assert_eq!(error.location.splat_index, Some(splat_index));
+    }
+    assert_eq!(errors[16].code, "invalid_rotation_mode");
+    assert_eq!(errors[16].message, format!("{} more splats", count - 16));
+}
+
+#[test]
+fn reports_every_broken_part() {
+    let mut files = sample_files();
+    let sh0 = file_name(&files, "sh0", 0);
+    files.remove(&sh0);
+    let mut meta = meta_json(&files);
+    meta["means"]["mins"] = serde_json::json!([0.0, 0.0]);
+    set_meta_json(&mut files, &meta);
+    files.insert("unused.webp".to_string(), Vec::new());
+
+    let report = validate(&archive(&files));
+    assert!(!report.is_valid());
+    let has = |severity: Severity, code: &str, entry: Option<&str>, field: Option<&str>| {
+        report.findings.iter().any(|f| {
+            f.severity == severity
+                && f.code == code
+                && (entry.is_none() || f.location.entry.as_deref() == entry)
+                && (field.is_none() || f.location.field.as_deref() == field)
+        })
+    };
+    assert!(
+        has(Severity::Error, "image_not_found", Some(&sh0), None),
+        "{:#?}",
+        report.findings
+    );
+    assert!(
+        has(Severity::Error, "invalid_vector", None, Some("means.mins")),
+        "{:#?}",
+        report.findings
+    );
+    assert!(
+        report
+            .warnings()
+            .any(|f| f.location.entry.as_deref() == Some("unused.webp")),
+        "{:#?}",
+        report.findings
+    );
+}